@@ -0,0 +1,67 @@
+use std::fmt;
+use std::io;
+use bio::io::fastq;
+
+/*
+Everything that can go wrong in a run that isn't a bug of the solver itself.
+Input : the input strings (or another file the run reads, ie: a saved index or previous output) can't be interpreted
+Parameter : the arguments of the run are invalid or don't go together
+Io : reading or writing a file failed
+io::Errors of kind InvalidData are the solver's own complaints about what it read, so they become Input errors.
+*/
+#[derive(Debug)]
+pub enum Error {
+    Input(String),
+    Parameter(String),
+    Io(io::Error),
+}
+
+impl Error {
+    // the exit code of the binary for this kind of error
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Parameter(_) => 2,
+            Error::Input(_) => 3,
+            Error::Io(_) => 4,
+        }
+    }
+
+    // as From<io::Error>, saying what was being done with which file
+    pub fn in_file(e : io::Error, doing : &str, path : &str) -> Error {
+        match e.kind() {
+            io::ErrorKind::InvalidData => Error::Input(e.to_string()),
+            kind => Error::Io(io::Error::new(kind, format!("Couldn't {} {}: {}", doing, path, e))),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e : io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::InvalidData => Error::Input(e.to_string()),
+            _ => Error::Io(e),
+        }
+    }
+}
+
+// malformed FASTQ records are Input errors, failing to read them an Io error
+impl From<fastq::Error> for Error {
+    fn from(e : fastq::Error) -> Error {
+        match e {
+            fastq::Error::ReadError(e) | fastq::Error::FileOpen{source : e, ..} => Error::from(e),
+            e => Error::Input(format!("Bad FASTQ record: {}", e)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Input(ref msg) => write!(f, "Invalid input. {}", msg),
+            Error::Parameter(ref msg) => write!(f, "{}", msg),
+            Error::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
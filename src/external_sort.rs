@@ -0,0 +1,296 @@
+use crate::structs::solutions::{Solution, Cigar};
+use crate::structs::run_config::{Config, Maps};
+use crate::useful::Orientation;
+use crate::index::{write_u64, read_u64};
+use crate::solution_comparator;
+
+use std::fs::{self, File};
+use std::io;
+use std::io::{Read, Write, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+// distinguishes the temporary files of sorters in the same process
+static SORTERS_CREATED : AtomicUsize = AtomicUsize::new(0);
+
+// by default, at most this many runs are merged at once (each an open file)
+const MAX_FAN_IN : usize = 64;
+
+/*
+Sorting and deduplicating the solutions with a fixed memory budget.
+Solutions are buffered until the buffer holds the given number of them. The buffer is then sorted
+(as for the output file, see solution_comparator) and written to a temporary file as a "run".
+Finishing merges the runs into the final order, skipping duplicates, so at any time only
+the buffer and one solution per merged run are held in memory. With more runs than the fan-in,
+groups of them are first merged into longer runs, until the rest can be merged at once.
+The temporary files are removed when the sorter is dropped, whether it finished or not.
+
+Runs are binary, each solution a fixed sequence of little-endian u64 values:
+[id_a][id_b][orientation][overhang_left_a][overhang_right_b][overlap_a][overlap_b][errors][cigar]
+where the cigar is its number of operations (u64::MAX if there is none) followed by (length, operation) pairs.
+*/
+pub struct ExternalSorter<'a> {
+    maps : &'a Maps,
+    config : &'a Config,
+    capacity : usize,
+    buffer : Vec<Solution>,
+    runs : Vec<(PathBuf, usize)>, //path, number of solutions
+    dir : PathBuf,
+    sorter_id : usize,
+    files_created : usize,
+    fan_in : usize,
+}
+
+impl<'a> ExternalSorter<'a> {
+    // runs are written to config.temp_dir, or the system's temporary directory
+    pub fn new(maps : &'a Maps, config : &'a Config, capacity : usize) -> ExternalSorter<'a> {
+        assert!(capacity > 0);
+        let dir = match config.temp_dir {
+            Some(ref dir) => PathBuf::from(dir),
+            None => std::env::temp_dir(),
+        };
+        ExternalSorter {
+            maps,
+            config,
+            capacity,
+            buffer : Vec::with_capacity(capacity),
+            runs : Vec::new(),
+            dir,
+            sorter_id : SORTERS_CREATED.fetch_add(1, AtomicOrdering::SeqCst),
+            files_created : 0,
+            fan_in : MAX_FAN_IN,
+        }
+    }
+
+    // at most this many runs are merged at once (and so, open at once). MAX_FAN_IN by default
+    pub fn fan_in(mut self, fan_in : usize) -> ExternalSorter<'a> {
+        assert!(fan_in >= 2);
+        self.fan_in = fan_in;
+        self
+    }
+
+    pub fn push(&mut self, solution : Solution) -> Result<(), io::Error> {
+        self.buffer.push(solution);
+        if self.buffer.len() >= self.capacity {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    pub fn num_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn sort_buffer(&mut self) {
+        let maps = self.maps;
+        self.buffer.sort_by(|a, b| solution_comparator(a, b, maps));
+        self.buffer.dedup_by(|x, y| solution_comparator(x, y, maps) == Ordering::Equal);
+    }
+
+    /*
+    a new, empty run at the end of the runs. It is kept track of before anything is written,
+    so that it is removed (see Drop) even if writing it fails.
+    */
+    fn create_run(&mut self) -> Result<BufWriter<File>, io::Error> {
+        let path = self.dir.join(format!("rust_overlaps_{}_{}_{}.run",
+                                         process::id(), self.sorter_id, self.files_created));
+        self.files_created += 1;
+        let f = File::create(&path)?;
+        self.runs.push((path, 0));
+        Ok(BufWriter::new(f))
+    }
+
+    fn spill(&mut self) -> Result<(), io::Error> {
+        self.sort_buffer();
+        let mut buf = self.create_run()?;
+        for solution in self.buffer.iter() {
+            write_solution(&mut buf, solution)?;
+        }
+        buf.flush()?;
+        let run = self.runs.last_mut().unwrap();
+        run.1 = self.buffer.len();
+        if self.config.verbosity >= 2 {
+            println!("OK spilled a sorted run of {} solutions to {:?}.", run.1, &run.0);
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /*
+    merges the first fan_in runs into a new one at the end of the runs, and removes them.
+    Runs are merged in the order they were written, so each merged run is about as long as the others of its pass.
+    */
+    fn merge_first_runs(&mut self) -> Result<(), io::Error> {
+        let mut buf = self.create_run()?;
+        let merged = self.merge(&self.runs[..self.fan_in], &mut |solution| write_solution(&mut buf, solution))?;
+        buf.flush()?;
+        self.runs.last_mut().unwrap().1 = merged;
+        for (path, _) in self.runs.drain(..self.fan_in) {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    /*
+    hands every unique solution to the given function in sorted order, stopping at the first error it returns.
+    returns the number of solutions handed over.
+    the temporary files are removed when the sorter is dropped, ie: once this returns (even if reading them failed).
+    */
+    pub fn finish<F>(mut self, mut f : F) -> Result<usize, io::Error>
+            where F : FnMut(&Solution) -> Result<(), io::Error> {
+        if self.runs.is_empty() {
+            // everything fit in memory
+            self.sort_buffer();
+            for solution in self.buffer.iter() {
+                f(solution)?;
+            }
+            return Ok(self.buffer.len());
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        self.buffer.shrink_to_fit();
+        while self.runs.len() > self.fan_in {
+            self.merge_first_runs()?;
+        }
+        self.merge(&self.runs, &mut f)
+    }
+
+    // hands every unique solution of the given runs to the given function in sorted order. returns how many
+    fn merge<F>(&self, runs : &[(PathBuf, usize)], f : &mut F) -> Result<usize, io::Error>
+            where F : FnMut(&Solution) -> Result<(), io::Error> {
+        let mut readers : Vec<(BufReader<File>, usize)> = Vec::with_capacity(runs.len());
+        for &(ref path, len) in runs.iter() {
+            readers.push((BufReader::new(File::open(path)?), len));
+        }
+        let mut heap : BinaryHeap<RunHead> = BinaryHeap::with_capacity(readers.len());
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(solution) = next_from_run(reader)? {
+                heap.push(RunHead{solution, run, maps : self.maps});
+            }
+        }
+        let mut last : Option<Solution> = None;
+        let mut written = 0;
+        while let Some(RunHead{solution, run, ..}) = heap.pop() {
+            if let Some(next) = next_from_run(&mut readers[run])? {
+                heap.push(RunHead{solution : next, run, maps : self.maps});
+            }
+            let duplicate = match last {
+                Some(ref l) => solution_comparator(l, &solution, self.maps) == Ordering::Equal,
+                None => false,
+            };
+            if !duplicate {
+                f(&solution)?;
+                written += 1;
+                last = Some(solution);
+            }
+        }
+        if self.config.verbosity >= 2 {
+            println!("OK merged {} sorted runs.", runs.len());
+        }
+        Ok(written)
+    }
+}
+
+impl<'a> Drop for ExternalSorter<'a> {
+    fn drop(&mut self) {
+        for (path, _) in self.runs.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// the smallest solution of a run that hasn't been merged yet. ordered in reverse for the max-heap
+struct RunHead<'a> {
+    solution : Solution,
+    run : usize,
+    maps : &'a Maps,
+}
+
+impl<'a> Ord for RunHead<'a> {
+    fn cmp(&self, other : &Self) -> Ordering {
+        solution_comparator(&other.solution, &self.solution, self.maps)
+            .then(other.run.cmp(&self.run))
+    }
+}
+
+impl<'a> PartialOrd for RunHead<'a> {
+    fn partial_cmp(&self, other : &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> PartialEq for RunHead<'a> {
+    fn eq(&self, other : &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a> Eq for RunHead<'a> { }
+
+fn next_from_run(reader : &mut (BufReader<File>, usize)) -> Result<Option<Solution>, io::Error> {
+    if reader.1 == 0 {
+        return Ok(None);
+    }
+    reader.1 -= 1;
+    read_solution(&mut reader.0).map(Some)
+}
+
+fn write_solution<W : Write>(buf : &mut W, s : &Solution) -> Result<(), io::Error> {
+    write_u64(buf, s.id_a as u64)?;
+    write_u64(buf, s.id_b as u64)?;
+    write_u64(buf, if s.orientation == Orientation::Normal {0} else {1})?;
+    write_u64(buf, s.overhang_left_a as i64 as u64)?;
+    write_u64(buf, s.overhang_right_b as i64 as u64)?;
+    write_u64(buf, s.overlap_a as u64)?;
+    write_u64(buf, s.overlap_b as u64)?;
+    write_u64(buf, s.errors as u64)?;
+    match s.cigar {
+        Some(ref cigar) => {
+            write_u64(buf, cigar.len() as u64)?;
+            for &(len, op) in cigar.iter() {
+                write_u64(buf, len as u64)?;
+                write_u64(buf, op as u64)?;
+            }
+        },
+        None => write_u64(buf, u64::MAX)?,
+    }
+    Ok(())
+}
+
+fn read_solution<R : Read>(buf : &mut R) -> Result<Solution, io::Error> {
+    let id_a = read_u64(buf)? as usize;
+    let id_b = read_u64(buf)? as usize;
+    let orientation = if read_u64(buf)? == 0 {Orientation::Normal} else {Orientation::Reversed};
+    let overhang_left_a = read_u64(buf)? as i64 as i32;
+    let overhang_right_b = read_u64(buf)? as i64 as i32;
+    let overlap_a = read_u64(buf)? as usize;
+    let overlap_b = read_u64(buf)? as usize;
+    let errors = read_u64(buf)? as u32;
+    let cigar_len = read_u64(buf)?;
+    let cigar = if cigar_len == u64::MAX {
+        None
+    } else {
+        let mut cigar : Cigar = Vec::with_capacity(cigar_len as usize);
+        for _ in 0..cigar_len {
+            let len = read_u64(buf)? as usize;
+            let op = read_u64(buf)? as u8;
+            cigar.push((len, op));
+        }
+        Some(cigar)
+    };
+    Ok(Solution{
+        id_a,
+        id_b,
+        orientation,
+        overhang_left_a,
+        overhang_right_b,
+        overlap_a,
+        overlap_b,
+        errors,
+        cigar,
+    })
+}
@@ -0,0 +1,174 @@
+use crate::structs::solutions::Solution;
+use crate::structs::run_config::{Config, Maps};
+use crate::useful::Orientation;
+use crate::output::{layout, Layout};
+use crate::solution_comparator;
+
+use std::collections::{HashMap, HashSet};
+
+/*
+Optional post-processing of the complete solution set, reducing it to what a layout step needs.
+Each filter only removes solutions, and the order of the remaining ones is kept. They are applied in this order:
+1. contained reads are removed along with all their solutions (--remove_contained)
+2. transitive overlaps are removed in the style of Myers' string graph (--transitive_reduction)
+3. only the best N overlaps at each end of each string are kept (--best_overlaps)
+Solutions are in their EXTERNAL representation, but are handled using the internal ids.
+*/
+pub fn apply(mut solutions : Vec<Solution>, maps : &Maps, config : &Config) -> Vec<Solution> {
+    let before = solutions.len();
+    if config.remove_contained {
+        solutions = remove_contained(solutions, config);
+    }
+    if config.transitive_reduction {
+        solutions = transitive_reduction(solutions, config);
+    }
+    if let Some(n) = config.best_overlaps {
+        solutions = best_overlaps(solutions, n, maps, config);
+    }
+    if config.verbosity >= 2 && solutions.len() < before {
+        println!("OK filtered out {} of {} solutions.", before - solutions.len(), before);
+    }
+    solutions
+}
+
+// the input string a (possibly reversed) id belongs to
+#[inline]
+pub(crate) fn read_of(id : usize, config : &Config) -> usize {
+    if config.reversals {id / 2} else {id}
+}
+
+/*
+vertices of the string graph are the input strings in either orientation: 2*read (forwards) and 2*read+1 (reversed).
+An edge u->v says the end of u overlaps the start of v, and v starts offset symbols after u.
+Each overlap of two string ends (dovetail) is a pair of edges: as the solution is oriented,
+and its reverse complement, in which the roles and overhangs of the strings swap.
+Containments are not edges.
+*/
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Edge {
+    pub from : usize,
+    pub to : usize,
+    pub offset : i32,
+}
+
+pub(crate) fn dovetail_edges(s : &Solution, config : &Config) -> Option<(Edge, Edge)> {
+    let a = 2 * read_of(s.id_a, config);
+    let b = 2 * read_of(s.id_b, config) + if s.orientation == Orientation::Reversed {1} else {0};
+    let flip = |v : usize| v ^ 1;
+    match layout(s) {
+        Layout::AThenB => Some((
+            Edge{from : a, to : b, offset : s.overhang_left_a},
+            Edge{from : flip(b), to : flip(a), offset : s.overhang_right_b},
+        )),
+        Layout::BThenA => Some((
+            Edge{from : b, to : a, offset : -s.overhang_left_a},
+            Edge{from : flip(a), to : flip(b), offset : -s.overhang_right_b},
+        )),
+        Layout::AContainsB | Layout::BContainsA => None,
+    }
+}
+
+/*
+a string is contained if it is included in another one. With identical extents, B counts as contained in A.
+*/
+pub(crate) fn contained_reads(solutions : &[Solution], config : &Config) -> HashSet<usize> {
+    let mut contained : HashSet<usize> = HashSet::new();
+    for s in solutions.iter() {
+        match layout(s) {
+            Layout::AContainsB => {contained.insert(read_of(s.id_b, config));},
+            Layout::BContainsA => {contained.insert(read_of(s.id_a, config));},
+            _ => (),
+        }
+    }
+    contained
+}
+
+fn remove_contained(mut solutions : Vec<Solution>, config : &Config) -> Vec<Solution> {
+    let contained = contained_reads(&solutions, config);
+    if config.verbosity >= 2 {
+        println!("OK found {} contained strings.", contained.len());
+    }
+    solutions.retain(|s| !contained.contains(&read_of(s.id_a, config))
+                      && !contained.contains(&read_of(s.id_b, config)));
+    solutions
+}
+
+/*
+an edge u->w is transitive if there is some v with edges u->v and v->w, and the offsets of the two
+add up to that of u->w (up to config.transitive_fuzz, as overlaps with indels don't add up exactly).
+A solution is removed if either of its edges is transitive.
+Containments are left alone, so this is best combined with --remove_contained.
+*/
+pub(crate) fn transitive_reduction(solutions : Vec<Solution>, config : &Config) -> Vec<Solution> {
+    let fuzz = config.transitive_fuzz as i32;
+    let mut out_edges : HashMap<usize, Vec<(Edge, usize)>> = HashMap::new();
+    for (index, s) in solutions.iter().enumerate() {
+        if let Some((forwards, backwards)) = dovetail_edges(s, config) {
+            out_edges.entry(forwards.from).or_default().push((forwards, index));
+            out_edges.entry(backwards.from).or_default().push((backwards, index));
+        }
+    }
+
+    let mut transitive = vec![false; solutions.len()];
+    for (_, edges) in out_edges.iter() {
+        for &(u_v, _) in edges.iter() {
+            let v_edges = match out_edges.get(&u_v.to) {
+                Some(v_edges) => v_edges,
+                None => continue,
+            };
+            for &(v_w, _) in v_edges.iter() {
+                for &(u_w, index) in edges.iter() {
+                    if u_w.to == v_w.to && u_w.to != u_v.to
+                            && (u_v.offset + v_w.offset - u_w.offset).abs() <= fuzz {
+                        transitive[index] = true;
+                    }
+                }
+            }
+        }
+    }
+    solutions.into_iter()
+        .zip(transitive)
+        .filter(|&(_, t)| !t)
+        .map(|(s, _)| s)
+        .collect()
+}
+
+/*
+each edge u->v uses the right end of u (as oriented) and the left end of v.
+For the input strings that is the right end of forward vertices and the left end of reversed ones (and vice versa).
+The overlaps at each string end are ranked by overlap length (the longer of the two), then by fewest errors,
+and a solution is kept if it is among the best N at either of its two ends.
+Containments don't use string ends and are all kept.
+*/
+fn best_overlaps(solutions : Vec<Solution>, n : usize, maps : &Maps, config : &Config) -> Vec<Solution> {
+    // (read, is right end) -> solutions at that end
+    let mut at_end : HashMap<(usize, bool), Vec<usize>> = HashMap::new();
+    let mut keep = vec![false; solutions.len()];
+    for (index, s) in solutions.iter().enumerate() {
+        match dovetail_edges(s, config) {
+            Some((edge, _)) => {
+                let from_end = (edge.from / 2, edge.from % 2 == 0);
+                let to_end = (edge.to / 2, edge.to % 2 == 1);
+                at_end.entry(from_end).or_default().push(index);
+                at_end.entry(to_end).or_default().push(index);
+            },
+            None => keep[index] = true,
+        }
+    }
+    for (_, indexes) in at_end.iter_mut() {
+        indexes.sort_by(|&x, &y| {
+            let (x, y) = (&solutions[x], &solutions[y]);
+            y.overlap_a.max(y.overlap_b).cmp(&x.overlap_a.max(x.overlap_b))
+                .then(x.errors.cmp(&y.errors))
+                .then(solution_comparator(x, y, maps))
+        });
+        for &index in indexes.iter().take(n) {
+            keep[index] = true;
+        }
+    }
+    solutions.into_iter()
+        .zip(keep)
+        .filter(|&(_, k)| k)
+        .map(|(s, _)| s)
+        .collect()
+}
@@ -0,0 +1,506 @@
+use bio::data_structures::bwt::{bwt, less, Occ, BWT, Less};
+use bio::data_structures::fmindex::{FMIndex, FMIndexable, Interval};
+use bio::data_structures::suffix_array::{suffix_array, RawSuffixArray};
+use bio::alphabets::Alphabet;
+use bidir_map::BidirMap;
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write, BufReader, BufWriter, Take};
+use std::ops::Range;
+
+use crate::structs::run_config::{Config, Maps};
+
+/*
+The FM index over the text of the maps (only the reference set, if queries were appended), and its persistence to disk.
+An index can also cover any other run of consecutive ids, in which case it locates positions
+of its part of the text, offset to the positions of the whole text.
+Building the suffix array dominates the setup of a run, but it only depends on the input
+(and how it was prepared), not on ERR_RATE, THRESH or the mode. So an index can be saved once
+and loaded again for any number of runs over the same input.
+
+The Occ table is not stored. It is rebuilt from the BWT in one linear pass when loading.
+
+Both the suffix array and the Occ table can be sampled to trade query speed for memory (config).
+*/
+
+static MAGIC : &[u8] = b"ROVLIDX\0";
+static FORMAT_VERSION : u64 = 3;
+
+pub struct Index {
+    pub sa : SampledSuffixArray,
+    pub bwt : BWT,
+    pub less : Less,
+    pub occ : Occ,
+    pub offset : usize, // of the indexed part of the text
+}
+
+/*
+the symbols of an indexed text: those of the strings and the separators '#' and '$'.
+Occ only counts the symbols of its alphabet, and the separators are looked up too (see BidirectionalIndex and
+SampledSuffixArray::get).
+*/
+fn text_alphabet(config : &Config) -> Alphabet {
+    Alphabet::new(b"#$".iter().chain(config.index_symbols().iter()))
+}
+
+impl Index {
+    pub fn build(maps : &Maps, config : &Config) -> Index {
+        Index::build_for(maps, maps.reference_ids(), config)
+    }
+
+    // indexes only the strings with the given ids
+    pub fn build_for(maps : &Maps, ids : Range<usize>, config : &Config) -> Index {
+        let alphabet = text_alphabet(config);
+        if config.verbosity >= 2 {
+            println!("OK index alphabet set to '{}'",
+                     String::from_utf8_lossy(config.index_symbols()));
+        }
+        let (offset, text) = maps.text_of(&ids);
+        let sa = suffix_array(&text);
+        let bwt = bwt(&text, &sa);
+        let less = less(&bwt, &alphabet);
+        let occ = Occ::new(&bwt, config.occ_sampling, &alphabet);
+        let sa = SampledSuffixArray::sample(sa, config.sa_sampling);
+        if config.verbosity >= 2 {
+            println!("OK index ready. Sampling rates: SA {}, Occ {}.", config.sa_sampling, config.occ_sampling);
+        };
+        Index {
+            sa,
+            bwt,
+            less,
+            occ,
+            offset,
+        }
+    }
+
+    pub fn fm_index(&self) -> FMIndex<&BWT, &Less, &Occ> {
+        FMIndex::new(&self.bwt, &self.less, &self.occ)
+    }
+
+    // text positions for the given suffix array interval (exclusive upper bound)
+    pub fn locate(&self, interval : &Interval) -> Vec<usize> {
+        let fm = self.fm_index();
+        (interval.lower..interval.upper)
+            .map(|sa_index| self.sa.get(sa_index, &fm) + self.offset)
+            .collect()
+    }
+}
+
+/*
+The FM index of the reversed text of an Index, which together with it forms a bidirectional index (2BWT).
+Only modes that extend searches in both directions need it (see IsMode::bidirectional).
+It isn't saved along with the index, but built for each run that needs it. Its suffix array isn't kept,
+as positions are always located through the forward index.
+*/
+pub struct ReverseIndex {
+    pub bwt : BWT,
+    pub less : Less,
+    pub occ : Occ,
+}
+
+impl ReverseIndex {
+    pub fn build(maps : &Maps, index : &Index, config : &Config) -> ReverseIndex {
+        let alphabet = text_alphabet(config);
+        // the indexed text without its terminating '#', reversed and terminated again
+        let mut text : Vec<u8> = maps.text[index.offset..(index.offset + index.bwt.len() - 1)].to_vec();
+        text.reverse();
+        text.push(b'#');
+        let sa = suffix_array(&text);
+        let bwt = bwt(&text, &sa);
+        let less = less(&bwt, &alphabet);
+        let occ = Occ::new(&bwt, config.occ_sampling, &alphabet);
+        if config.verbosity >= 2 {println!("OK reverse index ready.");}
+        ReverseIndex {
+            bwt,
+            less,
+            occ,
+        }
+    }
+}
+
+/*
+A section of the pattern matched in a bidirectional index. The suffixes of the text starting with it are the
+SA interval lower..lower+size, and the suffixes of the reversed text starting with its reverse are
+lower_rev..lower_rev+size.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiInterval {
+    pub lower : usize,
+    pub lower_rev : usize,
+    pub size : usize,
+}
+
+impl BiInterval {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    // the interval of the forward index (exclusive upper bound), ie: to locate the matches
+    #[inline]
+    pub fn forward(&self) -> Interval {
+        Interval {
+            lower : self.lower,
+            upper : self.lower + self.size,
+        }
+    }
+}
+
+/*
+An Index along with its ReverseIndex. A match can be extended to the left with the forward index and
+to the right with the reverse index, each time keeping the interval of the other index in sync:
+the matches extended with a symbol come after those extended with any smaller symbol (including '#' and '$')
+in the other index.
+*/
+pub struct BidirectionalIndex<'a> {
+    index : &'a Index,
+    reverse : &'a ReverseIndex,
+    symbols : Vec<u8>, // every symbol of the text, in sorted order
+}
+
+impl<'a> BidirectionalIndex<'a> {
+    pub fn new(index : &'a Index, reverse : &'a ReverseIndex, config : &Config) -> Self {
+        let mut symbols = vec![b'#', b'$'];
+        symbols.extend(config.index_symbols());
+        BidirectionalIndex {
+            index,
+            reverse,
+            symbols,
+        }
+    }
+
+    pub fn full_interval(&self) -> BiInterval {
+        BiInterval {
+            lower : 0,
+            lower_rev : 0,
+            size : self.index.bwt.len(),
+        }
+    }
+
+    // the matches of c followed by the matched section
+    pub fn extend_left(&self, interval : &BiInterval, c : u8) -> BiInterval {
+        let (lower, size, smaller) = extend(&self.index.bwt, &self.index.less, &self.index.occ,
+                                            interval.lower, interval.size, c, &self.symbols);
+        BiInterval {
+            lower,
+            lower_rev : interval.lower_rev + smaller,
+            size,
+        }
+    }
+
+    // the matches of the matched section followed by c
+    pub fn extend_right(&self, interval : &BiInterval, c : u8) -> BiInterval {
+        let (lower_rev, size, smaller) = extend(&self.reverse.bwt, &self.reverse.less, &self.reverse.occ,
+                                                interval.lower_rev, interval.size, c, &self.symbols);
+        BiInterval {
+            lower : interval.lower + smaller,
+            lower_rev,
+            size,
+        }
+    }
+}
+
+/*
+one step of backward search from the interval lower..lower+size of the given index.
+returns the lower bound and size of the interval of c followed by the match,
+and how many of the matches are preceded by a symbol smaller than c.
+*/
+fn extend(bwt : &BWT, less : &Less, occ : &Occ, lower : usize, size : usize, c : u8, symbols : &[u8])
+        -> (usize, usize, usize) {
+    let count = |a : u8| {
+        if size == 0 {
+            return 0;
+        }
+        let before = if lower > 0 {occ.get(bwt, lower - 1, a)} else {0};
+        occ.get(bwt, lower + size - 1, a) - before
+    };
+    let smaller : usize = symbols.iter().take_while(|&&a| a < c).map(|&a| count(a)).sum();
+    let before = if lower > 0 {occ.get(bwt, lower - 1, c)} else {0};
+    (less[c as usize] + before, count(c), smaller)
+}
+
+/*
+The suffix array, either complete (rate 1) or sampled at every k-th TEXT position.
+A missing entry is found by LF-walking the BWT from its SA index until reaching one whose
+text position is sampled, which takes at most k-1 steps.
+Only the sampled entries are kept, along with a bit vector marking which SA indexes they belong to.
+*/
+#[derive(Debug, PartialEq)]
+pub struct SampledSuffixArray {
+    rate : usize,
+    samples : Vec<usize>,
+    marks : Vec<u64>,   // bit i is set IFF SA[i] is sampled. empty for rate 1
+    ranks : Vec<usize>, // number of set bits in the words of marks before each word
+}
+
+impl SampledSuffixArray {
+    pub fn sample(sa : RawSuffixArray, rate : usize) -> SampledSuffixArray {
+        assert!(rate >= 1);
+        if rate == 1 {
+            return SampledSuffixArray{rate : 1, samples : sa, marks : vec![], ranks : vec![]};
+        }
+        let mut samples = Vec::with_capacity(sa.len() / rate + 1);
+        let mut marks = vec![0u64; sa.len().div_ceil(64)];
+        for (sa_index, &pos) in sa.iter().enumerate() {
+            if pos % rate == 0 {
+                samples.push(pos);
+                marks[sa_index / 64] |= 1 << (sa_index % 64);
+            }
+        }
+        samples.shrink_to_fit();
+        SampledSuffixArray::from_parts(rate, samples, marks)
+    }
+
+    fn from_parts(rate : usize, samples : Vec<usize>, marks : Vec<u64>) -> SampledSuffixArray {
+        let mut ranks = Vec::with_capacity(marks.len());
+        let mut rank = 0;
+        for word in marks.iter() {
+            ranks.push(rank);
+            rank += word.count_ones() as usize;
+        }
+        SampledSuffixArray{rate, samples, marks, ranks}
+    }
+
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    #[inline]
+    fn is_sampled(&self, sa_index : usize) -> bool {
+        self.marks[sa_index / 64] & (1 << (sa_index % 64)) != 0
+    }
+
+    #[inline]
+    fn rank(&self, sa_index : usize) -> usize {
+        let below = (1u64 << (sa_index % 64)) - 1;
+        self.ranks[sa_index / 64] + (self.marks[sa_index / 64] & below).count_ones() as usize
+    }
+
+    // text position of the suffix at the given SA index
+    #[inline]
+    pub fn get<F : FMIndexable>(&self, mut sa_index : usize, fm : &F) -> usize {
+        if self.rate == 1 {
+            return self.samples[sa_index];
+        }
+        let mut steps = 0;
+        while !self.is_sampled(sa_index) {
+            // LF mapping: the SA index of the suffix one position further left in the text
+            let c = fm.bwt()[sa_index];
+            sa_index = fm.less(c) + fm.occ(sa_index, c) - 1;
+            steps += 1;
+        }
+        self.samples[self.rank(sa_index)] + steps
+    }
+}
+
+/*
+writes the maps and the index to a file. The header records everything a loaded index has to
+agree on with the config of the run that loads it.
+[magic][version][reversals][alphabet][complements][input path] [names][id indexes][text] [sa rate][sa samples][sa marks][bwt][less]
+*/
+pub fn save(filename : &str, maps : &Maps, index : &Index, config : &Config) -> Result<(), io::Error> {
+    if maps.first_query_id != 0 || index.offset != 0 || index.bwt.len() != maps.text.len() {
+        return Err(invalid("only an index of all the strings in the maps can be saved.".to_owned()));
+    }
+    let f = File::create(filename)?;
+    let mut buf = BufWriter::new(f);
+    buf.write_all(MAGIC)?;
+    write_u64(&mut buf, FORMAT_VERSION)?;
+    write_u64(&mut buf, if config.reversals {1} else {0})?;
+    write_bytes(&mut buf, config.index_symbols())?;
+    write_bytes(&mut buf, config.alphabet.complements.as_deref().unwrap_or(b""))?;
+    write_bytes(&mut buf, config.indexed_input().as_bytes())?;
+
+    write_u64(&mut buf, maps.num_ids() as u64)?;
+    for name in maps.id2name_vec.iter() {
+        write_bytes(&mut buf, name.as_bytes())?;
+    }
+    for id in 0..maps.num_ids() {
+        write_u64(&mut buf, maps.index_for(id) as u64)?;
+    }
+    write_bytes(&mut buf, &maps.text)?;
+
+    write_u64(&mut buf, index.sa.rate as u64)?;
+    write_usizes(&mut buf, &index.sa.samples)?;
+    write_u64s(&mut buf, &index.sa.marks)?;
+    write_bytes(&mut buf, &index.bwt)?;
+    write_usizes(&mut buf, &index.less)?;
+    buf.flush()?;
+    if config.verbosity >= 2 {println!("OK index saved to {}.", filename);}
+    Ok(())
+}
+
+/*
+reads back what save() wrote, checking that the index is usable for a run with the given config.
+Every length read from the file is checked against what is left of it, so a corrupt file is InvalidData
+rather than a huge allocation.
+*/
+pub fn load(filename : &str, config : &Config) -> Result<(Maps, Index), io::Error> {
+    let f = File::open(filename)?;
+    let size = f.metadata()?.len();
+    let mut buf = BufReader::new(f).take(size);
+    let mut magic = [0u8; 8];
+    buf.read_exact(&mut magic)?;
+    if &magic[..] != MAGIC {
+        return Err(invalid(format!("{} is not an index file.", filename)));
+    }
+    let version = read_u64(&mut buf)?;
+    if version != FORMAT_VERSION {
+        return Err(invalid(format!("index file has format version {}, expected {}.", version, FORMAT_VERSION)));
+    }
+    let reversals = read_u64(&mut buf)? == 1;
+    if reversals != config.reversals {
+        return Err(invalid(format!("index was built {} reversals, but this run is {} reversals (flag -r).",
+                                   if reversals {"with"} else {"without"},
+                                   if config.reversals {"with"} else {"without"})));
+    }
+    let alphabet = read_bytes(&mut buf)?;
+    if &alphabet[..] != config.index_symbols() {
+        return Err(invalid(format!("index was built with alphabet '{}', but this run uses '{}' (flag -n).",
+                                   String::from_utf8_lossy(&alphabet),
+                                   String::from_utf8_lossy(config.index_symbols()))));
+    }
+    // the reverse complements in the text are those of the alphabet it was built with
+    let complements = read_bytes(&mut buf)?;
+    if &complements[..] != config.alphabet.complements.as_deref().unwrap_or(b"") {
+        return Err(invalid(format!("index was built with complements '{}', but this run uses '{}' (alphabet {}).",
+                                   String::from_utf8_lossy(&complements),
+                                   String::from_utf8_lossy(config.alphabet.complements.as_deref().unwrap_or(b"")),
+                                   config.alphabet.name)));
+    }
+    let input = String::from_utf8_lossy(&read_bytes(&mut buf)?).into_owned();
+    if config.update.is_none() && input != config.indexed_input() {
+        println!("    WARNING\n\tIndex was built from input {:?} but the run was given {:?}.\n\t\
+        The input file is not read. Solutions are for the indexed input.", input, config.indexed_input());
+    }
+
+    // each id has (at least) the length of its name and its index
+    let num_ids = read_len(&mut buf, 16)?;
+    let mut id2name_vec : Vec<String> = Vec::with_capacity(num_ids);
+    for _ in 0..num_ids {
+        id2name_vec.push(String::from_utf8_lossy(&read_bytes(&mut buf)?).into_owned());
+    }
+    let mut id2index_bdmap : BidirMap<usize, usize> = BidirMap::new();
+    let mut indexes : Vec<usize> = Vec::with_capacity(num_ids);
+    for id in 0..num_ids {
+        let index = read_u64(&mut buf)? as usize;
+        id2index_bdmap.insert(id, index);
+        indexes.push(index);
+    }
+    indexes.sort();
+    let text = read_bytes(&mut buf)?;
+    // the text ends with the one '#' and each string starts just after its '$' (see MapsBuilder)
+    if text.last() != Some(&b'#') {
+        return Err(invalid(format!("index file {} has a text that doesn't end with '#'.", filename)));
+    }
+    if indexes.windows(2).any(|pair| pair[0] == pair[1])
+            || indexes.iter().any(|&index| index == 0 || index >= text.len() || text[index - 1] != b'$') {
+        return Err(invalid(format!("index file {} has string indexes that don't start strings of its text.", filename)));
+    }
+    let maps = Maps{
+        text,
+        id2name_vec,
+        id2index_bdmap,
+        indexes,
+        first_query_id : 0,
+    };
+
+    let sa_rate = read_u64(&mut buf)? as usize;
+    let sa_samples = read_usizes(&mut buf)?;
+    let sa_marks = read_u64s(&mut buf)?;
+    let bwt = read_bytes(&mut buf)?;
+    let less = read_usizes(&mut buf)?;
+    if sa_rate == 0 || bwt.len() != maps.text.len()
+            || (sa_rate == 1 && sa_samples.len() != maps.text.len())
+            || (sa_rate > 1 && sa_marks.len() != maps.text.len().div_ceil(64))
+            // every marked SA index has a sample (see SampledSuffixArray::rank)
+            || (sa_rate > 1 && sa_marks.iter().map(|word| word.count_ones() as usize).sum::<usize>() != sa_samples.len())
+            || sa_samples.iter().any(|&sample| sample >= maps.text.len()) {
+        return Err(invalid(format!("index file {} is truncated or corrupt.", filename)));
+    }
+    // less is indexed by the symbols themselves, up to the greatest of the text's alphabet (see bio's bwt::less)
+    let symbols = text_alphabet(config);
+    let less_len = symbols.max_symbol().map_or(0, |c| c as usize + 2);
+    if less.len() != less_len {
+        return Err(invalid(format!("index file {} has {} entries of less, expected {}.", filename, less.len(), less_len)));
+    }
+    if sa_rate != config.sa_sampling {
+        println!("    WARNING\n\tIndex was saved with suffix array sampling rate {}. It is used instead of {}.",
+                 sa_rate, config.sa_sampling);
+    }
+    let sa = SampledSuffixArray::from_parts(sa_rate, sa_samples, sa_marks);
+    let occ = Occ::new(&bwt, config.occ_sampling, &symbols);
+    if config.verbosity >= 2 {println!("OK index loaded from {}.", filename);}
+    Ok((maps, Index{
+        sa,
+        bwt,
+        less,
+        occ,
+        offset : 0,
+    }))
+}
+
+fn invalid(msg : String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn write_u64<W : Write>(buf : &mut W, x : u64) -> Result<(), io::Error> {
+    buf.write_all(&x.to_le_bytes())
+}
+
+fn write_bytes<W : Write>(buf : &mut W, xs : &[u8]) -> Result<(), io::Error> {
+    write_u64(buf, xs.len() as u64)?;
+    buf.write_all(xs)
+}
+
+fn write_usizes<W : Write>(buf : &mut W, xs : &[usize]) -> Result<(), io::Error> {
+    write_u64(buf, xs.len() as u64)?;
+    for &x in xs {
+        write_u64(buf, x as u64)?;
+    }
+    Ok(())
+}
+
+fn write_u64s<W : Write>(buf : &mut W, xs : &[u64]) -> Result<(), io::Error> {
+    write_u64(buf, xs.len() as u64)?;
+    for &x in xs {
+        write_u64(buf, x)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_u64<R : Read>(buf : &mut R) -> Result<u64, io::Error> {
+    let mut bytes = [0u8; 8];
+    buf.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// a length read from the file, of entries taking entry_size bytes each, that must fit in what is left of it
+fn read_len<R : Read>(buf : &mut Take<R>, entry_size : u64) -> Result<usize, io::Error> {
+    let len = read_u64(buf)?;
+    if len.checked_mul(entry_size).is_none_or(|bytes| bytes > buf.limit()) {
+        return Err(invalid(format!("index file is truncated or corrupt. It can't hold the {} entries it claims.", len)));
+    }
+    Ok(len as usize)
+}
+
+fn read_bytes<R : Read>(buf : &mut Take<R>) -> Result<Vec<u8>, io::Error> {
+    let len = read_len(buf, 1)?;
+    let mut xs = vec![0u8; len];
+    buf.read_exact(&mut xs)?;
+    Ok(xs)
+}
+
+fn read_usizes<R : Read>(buf : &mut Take<R>) -> Result<Vec<usize>, io::Error> {
+    Ok(read_u64s(buf)?.into_iter().map(|x| x as usize).collect())
+}
+
+fn read_u64s<R : Read>(buf : &mut Take<R>) -> Result<Vec<u64>, io::Error> {
+    let len = read_len(buf, 8)?;
+    let mut xs = Vec::with_capacity(len);
+    for _ in 0..len {
+        xs.push(read_u64(buf)?);
+    }
+    Ok(xs)
+}
@@ -0,0 +1,411 @@
+use std::borrow::Borrow;
+use bio::data_structures::bwt::{BWT, Less, Occ};
+use bio::data_structures::fmindex::FMIndex;
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::time::Instant;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{thread, time};
+use std::io::stdout;
+
+////////////////////////////////////////////////////////////////////////
+
+pub mod prepare;
+pub mod index;
+pub mod structs;
+pub mod modes;
+pub mod output;
+pub mod external_sort;
+pub mod filter;
+pub mod string_graph;
+pub mod error;
+mod search;
+mod verification;
+mod testing;
+mod useful;
+
+pub use crate::structs::solutions::Solution;
+pub use crate::structs::run_config::{Config, Maps};
+pub use crate::modes::{IsMode, Mode};
+pub use crate::useful::Orientation;
+pub use crate::index::Index;
+pub use crate::error::Error;
+use crate::index::{ReverseIndex, BidirectionalIndex};
+pub use crate::external_sort::ExternalSorter;
+use crate::search::GeneratesCandidates;
+
+static ATOMIC_TASKS_DONE: AtomicUsize = AtomicUsize::new(0);
+
+/*
+The in-process entry point of the solver.
+Borrows the config and the maps (ie: the prepared reads) and owns the mode.
+An index for the maps can be handed over too (ie: one loaded from disk), otherwise one is built. Nothing is written to disk;
+the solutions are handed back to the caller, either as a sorted & deduplicated Vec (solve)
+or as unordered batches straight out of the worker threads (for_each).
+
+let maps = prepare::prepare_reads(reads, &config)?;
+let solutions = Overlapper::new(&config, &maps).mode(modes::get_mode("kucherov_2")?)?.solve();
+*/
+pub struct Overlapper<'a> {
+    config : &'a Config,
+    maps : &'a Maps,
+    mode : Mode,
+    index : Option<&'a Index>,
+}
+
+impl<'a> Overlapper<'a> {
+
+    pub fn new(config : &'a Config, maps : &'a Maps) -> Self {
+        Overlapper {
+            config,
+            maps,
+            mode : modes::default_mode(),
+            index : None,
+        }
+    }
+
+    // a Parameter error if the mode can't find all overlaps with this config (see modes::check_mode)
+    pub fn mode(mut self, mode : Mode) -> Result<Self, Error> {
+        modes::check_mode(mode.as_ref(), self.config)?;
+        self.mode = mode;
+        Ok(self)
+    }
+
+    // the index must have been built (or loaded) for these maps
+    pub fn index(mut self, index : &'a Index) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        self.config
+    }
+
+    pub fn maps(&self) -> &Maps {
+        self.maps
+    }
+
+    /*
+    1. build index from text (unless one was given)
+    2. generate tasks for each FORWARD query string in the text (ie: patterns)
+    3. spawn workers in a threadpool to solve tasks
+    4. hand each task's solutions to the aggregator as they come in
+    No order is guaranteed, and the same solution may be handed over more than once
+    (found from both sides of the overlap).
+    With a reference set (see prepare::append_queries), only with inclusions or without reversals:
+    another index is built for the queries, and each reference string is searched for among them too
+    (finding the inclusions of reference strings in queries, and overlaps from queries to reference strings).
+    Every pair of a query and a reference string is then searched for from both sides, as in a run over all the strings.
+    */
+    pub fn for_each<F>(&self, mut aggregator : F)
+            where F : FnMut(HashSet<Solution>) + Send + Sync {
+        let maps = self.maps;
+        let built_index;
+        let index = match self.index {
+            Some(index) => index,
+            None => {
+                built_index = Index::build(maps, self.config);
+                &built_index
+            },
+        };
+        let references_searched = maps.first_query_id != 0 && (self.config.inclusions || !self.config.reversals);
+        self.for_each_task(index, maps.query_ids(), references_searched, &mut aggregator);
+        if references_searched {
+            let query_index = Index::build_for(maps, maps.query_ids(), self.config);
+            self.for_each_task(&query_index, maps.reference_ids(), true, &mut aggregator);
+        }
+    }
+
+    /*
+    as for_each, with tasks for the given ids, searched for in the given index.
+    references_searched : whether the reference strings get tasks of their own too (see search::PatternConstants)
+    */
+    fn for_each_task<F>(&self, index : &Index, id_iterator : Range<usize>, references_searched : bool, mut aggregator : F)
+            where F : FnMut(HashSet<Solution>) + Send + Sync {
+        let config = self.config;
+        let maps = self.maps;
+        let mode = &self.mode;
+        let fm = index.fm_index();
+        // only modes extending searches in both directions need the index of the reversed text too
+        let reverse = if mode.bidirectional() && !config.is_exact() {Some(ReverseIndex::build(maps, index, config))} else {None};
+        let bidirectional = reverse.as_ref().map(|reverse| BidirectionalIndex::new(index, reverse, config));
+
+        let config_task_completion_clone = config.track_progress;
+        let num_tasks = id_iterator.len();
+        ATOMIC_TASKS_DONE.store(0, Ordering::SeqCst);
+
+        let progress_tracker = thread::spawn(move || {
+            track_progress(config_task_completion_clone, num_tasks);
+        }); // spawn progress-tracker thread
+        if config.track_progress {
+            if config.verbosity >= 2 {println!("OK spawning progress tracker thread.");}
+        }else{
+            if config.verbosity >= 2 {println!("OK suppressing progress tracker thread.");}
+        }
+        if config.verbosity >= 2 {println!("OK spawning {} worker threads.", config.worker_threads);}
+
+        if config.is_exact() && config.verbosity >= 2 {println!("OK no errors allowed. Searching for exact overlaps only.");}
+
+        if config.verbosity >= 1{
+            println!("OK working.");
+        }
+        let computation = |id_a|  solve_an_id(config, maps, id_a, index, &fm, bidirectional.as_ref(), mode, references_searched);
+        let task_aggregator = |solutions| {
+            aggregator(solutions);
+            if config.track_progress { ATOMIC_TASKS_DONE.fetch_add(1, Ordering::SeqCst);}
+        };
+        cue::pipeline(
+            "overlap_pipeline",          // name of the pipeline for logging
+             config.worker_threads,      // number of worker threads
+             id_iterator,                // iterator with work items
+             computation,
+             task_aggregator,
+        );
+
+        if config.track_progress {
+            ATOMIC_TASKS_DONE.store(num_tasks, Ordering::Relaxed);
+        }
+        progress_tracker.join().unwrap();
+    }
+
+    /*
+    workers ==> solutions --> sorted_solutions
+    returns the complete solution set, unique and sorted as in the output file.
+    */
+    pub fn solve(&self) -> Vec<Solution> {
+        let mut complete_solution_list : Vec<Solution> = Vec::new();
+        self.for_each(|solutions| {
+            complete_solution_list.extend(solutions);
+        });
+        self.sort_and_dedup(complete_solution_list)
+    }
+
+    /*
+    for maps of a previous read set with new strings appended as queries (see prepare::append_queries),
+    returns only the solutions involving at least one new string, unique and sorted as in the output file.
+    The index (given, or built) is that of the previous strings. Another is built for the new strings, and:
+    1. each new string is searched for among the new strings
+    2. each previous string is searched for among the new strings
+    3. only with inclusions or without reversals: each new string is searched for among the previous strings
+       (finding inclusions of new strings in previous ones, and overlaps the other way around)
+    Every pair of strings is searched for from the same side(s) as in a run over all the strings,
+    so the solutions are exactly those of such a run that involve a new string.
+    */
+    pub fn solve_update(&self) -> Vec<Solution> {
+        let maps = self.maps;
+        let built_index;
+        let index = match self.index {
+            Some(index) => index,
+            None => {
+                built_index = Index::build(maps, self.config);
+                &built_index
+            },
+        };
+        let new_index = Index::build_for(maps, maps.query_ids(), self.config);
+        let mut complete_solution_list : Vec<Solution> = Vec::new();
+        self.for_each_task(&new_index, maps.query_ids(), true, |solutions| complete_solution_list.extend(solutions));
+        self.for_each_task(&new_index, maps.reference_ids(), true, |solutions| complete_solution_list.extend(solutions));
+        if self.config.inclusions || !self.config.reversals {
+            self.for_each_task(index, maps.query_ids(), true, |solutions| complete_solution_list.extend(solutions));
+        }
+        self.sort_and_dedup(complete_solution_list)
+    }
+
+    fn sort_and_dedup(&self, mut complete_solution_list : Vec<Solution>) -> Vec<Solution> {
+        complete_solution_list.sort_by(|a, b| solution_comparator(a, b, self.maps));
+        if self.config.verbosity >= 2 {println!("OK output list sorted.");}
+        complete_solution_list.dedup_by(|x, y| solution_comparator(x, y, self.maps) == std::cmp::Ordering::Equal);
+        if self.config.verbosity >= 2 {println!("OK output list deduplicated.");}
+        complete_solution_list
+    }
+}
+
+/*
+Runs the solver and writes the solutions to the output file in the config.
+with -g solutions are written as the workers finish their tasks,
+otherwise they are first collected, sorted and deduplicated.
+(in memory, or in bounded memory using temporary files with --sort_buffer)
+With --update only the solutions involving new strings are found, and merged into the previous output.
+Only solutions collected in memory can be filtered (see filter::apply) and assembled into unitigs.
+Fails if the output (or a file it is merged from) can't be written (or read).
+*/
+pub fn solve(config : &Config, maps : &Maps, mode : Mode) -> Result<(), Error> {
+    let index = Index::build(maps, config);
+    solve_indexed(config, maps, &index, mode)
+}
+
+// as solve(), with an index that was built or loaded beforehand
+pub fn solve_indexed(config : &Config, maps : &Maps, index : &Index, mode : Mode) -> Result<(), Error> {
+    let output_error = |e : io::Error| Error::in_file(e, "write output file", &config.output);
+    let sort_error = |e : io::Error| Error::Io(io::Error::new(e.kind(), format!("Couldn't sort the solutions in temporary files: {}", e)));
+    let f = File::create(&config.output)
+        .map_err(|e| Error::in_file(e, "create output file", &config.output))?;
+    let mut wrt_buf = BufWriter::new(f);
+    output::write_preamble(&mut wrt_buf, maps, config).map_err(output_error)?;
+    if config.verbosity >= 2 {println!("OK wrote {:?} preamble to output file.", config.output_format);}
+    if config.verbosity >= 2 {println!("OK output writer ready.");}
+
+    let work_start = Instant::now();
+    let overlapper = Overlapper::new(config, maps).mode(mode)?.index(index);
+    if config.greedy_output {
+        //workers ==> out
+        // the workers can't be stopped, so only the first error is kept (and nothing more is written)
+        let mut written : io::Result<()> = Ok(());
+        overlapper.for_each(|solutions| {
+            if written.is_ok() {
+                written = solutions.iter()
+                    .try_for_each(|sol| output::write_solution(&mut wrt_buf, sol, maps, config))
+                    .and_then(|_| wrt_buf.flush());
+            }
+        });
+        written.map_err(output_error)?;
+    }else if let Some(capacity) = config.sort_buffer {
+        //workers ==> solutions --> sorted runs on disk --> merged --> out
+        let mut sorter = ExternalSorter::new(maps, config, capacity);
+        let mut spilled : io::Result<()> = Ok(());
+        overlapper.for_each(|solutions| {
+            if spilled.is_ok() {
+                spilled = solutions.into_iter().try_for_each(|sol| sorter.push(sol));
+            }
+        });
+        spilled.map_err(sort_error)?;
+        if config.verbosity >= 2 {println!("OK spilled {} sorted runs.", sorter.num_runs());}
+        let written = sorter.finish(|sol| output::write_solution(&mut wrt_buf, sol, maps, config))
+            .map_err(sort_error)?;
+        if config.verbosity >= 1{
+            println!("OK wrote {} solutions.", written);
+        }
+    }else if let Some(ref previous) = config.update {
+        //workers ==> new solutions --> sorted_solutions --> merged with previous output --> out
+        let new_solutions = overlapper.solve_update();
+        let written = output::write_merged(&mut wrt_buf, previous, &new_solutions, maps, config)
+            .map_err(|e| Error::in_file(e, "merge with previous output", previous))?;
+        if config.verbosity >= 1{
+            println!("OK wrote {} solutions, {} of them new.", written, new_solutions.len());
+        }
+    }else {
+        //workers ==> solutions --> sorted_solutions --> filtered --> out
+        let complete_solution_list = filter::apply(overlapper.solve(), maps, config);
+        for sol in complete_solution_list.iter(){
+            output::write_solution(&mut wrt_buf, sol, maps, config).map_err(output_error)?;
+        }
+        if config.verbosity >= 1{
+            println!("OK wrote {} solutions.", complete_solution_list.len());
+        }
+        if let Some(ref prefix) = config.unitigs {
+            let num_unitigs = string_graph::write_unitigs(prefix, &complete_solution_list, maps, config)
+                .map_err(|e| Error::in_file(e, "write unitigs to", prefix))?;
+            if config.verbosity >= 1{
+                println!("OK wrote {} unitigs to {}.fasta and {}.gfa.", num_unitigs, prefix, prefix);
+            }
+        }
+    }
+    wrt_buf.flush().map_err(output_error)?;
+    if config.verbosity >= 2 {println!("OK output file {} written.", config.output);};
+    if config.verbosity >= 1{
+        println!("OK completed in {}.", approx_elapsed_string(&work_start));
+    }
+    Ok(())
+}
+
+
+pub fn solution_comparator(x : &Solution, y : &Solution, maps : &Maps) -> std::cmp::Ordering{
+    (maps.get_name_for(x.id_a), maps.get_name_for(x.id_b), &x.orientation, x.overhang_left_a, x.overhang_right_b, x.overlap_a, x.overlap_b)
+        .cmp(&(maps.get_name_for(y.id_a), maps.get_name_for(y.id_b), &y.orientation, y.overhang_left_a, y.overhang_right_b, y.overlap_a, y.overlap_b))
+
+}
+
+
+fn approx_elapsed_string(start_time : &Instant) -> String{
+    time_display(Instant::elapsed(start_time).as_secs())
+}
+
+
+/*
+If the user enables it, this time keeper process will print a nice progress bar
+and ETA to STDOUT using carriage returns.
+*/
+fn track_progress(enabled : bool, num_tasks : usize) {
+    let my_start_time = Instant::now();
+    if !enabled {
+        return;
+    }
+    let chars = 30;
+    let mut complete = String::new();
+    let mut incomplete = String::new();
+    for _ in 0..chars{ incomplete.push(' ');}
+    let sleep_time = time::Duration::from_millis(500);
+    let mut tick_modulo = 0;
+    let tick_out_freq = 8;
+    let mut redraw = true;
+
+    loop{
+        let tasks_done = ATOMIC_TASKS_DONE.load(Ordering::Relaxed);
+        while  tasks_done as f32 / (num_tasks as f32) > complete.len() as f32/ ((complete.len() + incomplete.len()) as f32){
+            redraw = true;
+            tick_modulo = -1;
+            incomplete.pop();
+            complete.push('#');
+        }
+        tick_modulo += 1;
+        if redraw || tick_modulo == tick_out_freq {
+            let elapsed = Instant::elapsed(&my_start_time).as_secs();
+            let eta = elapsed as f32 * ((num_tasks-tasks_done) as f32) / (tasks_done as f32 + 0.2);
+            let eta_str = time_display(eta as u64);
+            print!("\r[{}{}] {}/{} tasks done. ETA {}                     ",
+                   &complete, &incomplete, tasks_done, num_tasks, eta_str);
+            stdout().flush().unwrap();
+            redraw = false;
+            tick_modulo = 0;
+        }
+        if tasks_done >= num_tasks{
+            println!("\r[{}{}] {}/{} tasks done.                            ",
+                     &complete, &incomplete, tasks_done, num_tasks);
+            stdout().flush().unwrap();
+            break;
+        }
+        thread::sleep(sleep_time);
+    }
+}
+
+
+fn time_display(sec : u64) -> String{
+    match sec {
+        0 => "< 1 sec".to_owned(),
+        x if x < 200 => format!("~{} sec", x),
+        x if x < 60*120 => format!("~{} min", x/60),
+        x if x < 60*60*100 => format!("~{} hrs", x/60/60),
+        x if x < 60*60*24*3 => format!("~{} days", x/60/60/24),
+        x if x < 60*60*24*7*3 => format!("~{} weeks", x/60/60/24/7),
+        x if x < 60*60*24*30*4 => format!("~{} months", x/60/60/24/30),
+        x if x < 60*60*24*365*5 => format!("~{} years", x/60/60/24/30),
+        _ => "eternity".to_owned(),
+    }
+}
+
+/*
+This is one task.
+essentially converts an ID (and some constant information)
+into a set of solutions involved with that ID.
+*/
+#[allow(clippy::too_many_arguments)]
+#[inline]
+fn solve_an_id<DBWT: Borrow<BWT>, DLess: Borrow<Less>, DOcc: Borrow<Occ>>
+        (config : &Config, maps : &Maps, id_a : usize, index : &Index,
+         fm : &FMIndex<DBWT, DLess, DOcc>, bidirectional : Option<&BidirectionalIndex>, mode : &Mode, references_searched : bool)
+                -> HashSet<Solution>{
+    if config.is_exact() {
+        let candidates = fm.generate_exact_candidates(maps.get_string(id_a), config, maps, id_a, index, mode, references_searched);
+        return verification::accept_all(id_a, candidates, config, maps);
+    }
+    let candidates = fm.generate_candidates(maps.get_string(id_a), config, maps, id_a, index, mode, references_searched, bidirectional);
+    verification::verify_all(id_a, candidates, config, maps)
+}
+
+impl<DBWT: Borrow<BWT>, DLess: Borrow<Less>, DOcc: Borrow<Occ>> GeneratesCandidates
+                    for FMIndex<DBWT, DLess, DOcc> {
+    //empty
+}
@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+use crate::modes::{self, Mode};
+use crate::search::GeneratesCandidates;
+use crate::structs::run_config::{Config, Maps};
+use crate::index::Index;
+use crate::verification;
+
+// the modes -m=auto chooses from. bidirectional modes are left out, as they need an index of their own to be built
+const CANDIDATE_MODES : [&str; 5] = ["valimaki", "kucherov_1", "kucherov_2", "kucherov_3", "kucherov_4"];
+
+// how many of the query strings are searched for with each of them
+const SAMPLE_SIZE : usize = 200;
+
+/*
+-m=auto. Chooses the mode for a run with the given config, once its strings are prepared and indexed.
+Every candidate mode searches for the same sample of query strings (evenly spread over them), one after another,
+and the mode spending the least time generating and verifying candidates is chosen. Its solutions are the same as any
+other's, so it is only a matter of speed.
+Without errors the modes aren't used at all (see Config::is_exact), so the default is returned straight away.
+*/
+pub fn select(config : &Config, maps : &Maps, index : &Index) -> Mode {
+    if config.is_exact() {
+        if config.verbosity >= 1 {println!("OK auto mode not needed without errors. Using the default.");}
+        return modes::default_mode();
+    }
+    let query_ids = maps.query_ids();
+    let step = (query_ids.len() / SAMPLE_SIZE).max(1);
+    let sample : Vec<usize> = query_ids.step_by(step).take(SAMPLE_SIZE).collect();
+    let fm = index.fm_index();
+
+    let mut fastest : Option<(Mode, Duration)> = None;
+    for name in CANDIDATE_MODES.iter() {
+        let mode = modes::get_mode(name).expect("the candidate modes are valid");
+        let mut generating = Duration::default();
+        let mut verifying = Duration::default();
+        for &id_a in sample.iter() {
+            let start = Instant::now();
+            let candidates = fm.generate_candidates(maps.get_string(id_a), config, maps, id_a, index, &mode, false, None);
+            generating += start.elapsed();
+            let start = Instant::now();
+            verification::verify_all(id_a, candidates, config, maps);
+            verifying += start.elapsed();
+        }
+        if config.verbosity >= 2 {
+            println!("OK auto mode timed {} on {} strings. {:?} generating and {:?} verifying candidates.",
+                     mode, sample.len(), generating, verifying);
+        }
+        if fastest.as_ref().is_none_or(|&(_, time)| generating + verifying < time) {
+            fastest = Some((mode, generating + verifying));
+        }
+    }
+    let (mode, time) = fastest.unwrap();
+    if config.verbosity >= 1 {
+        println!("OK auto mode selected {} for error rate {} and threshold {} ({:?} on {} sampled strings).",
+                 mode, config.err_rate, config.thresh, time, sample.len());
+    }
+    mode
+}
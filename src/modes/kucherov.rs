@@ -1,5 +1,6 @@
 use std::cmp::{min, max};
 use crate::modes::IsMode;
+use crate::error::Error;
 use std::fmt;
 
 #[derive(Debug)]
@@ -8,14 +9,16 @@ pub struct KucherovMode {
 }
 
 impl KucherovMode {
-    pub fn new(args : &[&str]) -> Self{
+    pub fn new(args : &[&str]) -> Result<Self, Error>{
         if args.len() != 1{
-            panic!("Expecting one numeric argument as Kucherov's S parameter!");
+            return Err(Error::Parameter("Expecting one numeric argument as Kucherov's S parameter!".to_owned()));
         }
         let s_param : i32 = args[0].parse()
-            .expect("Couldn't interpret the argument as a number!");
-        assert!(s_param >= 1, "Kucherov's S parameter needs to be >= 1");
-        KucherovMode {s_param : s_param}
+            .map_err(|_| Error::Parameter(format!("Couldn't interpret Kucherov's S parameter '{}' as a number!", args[0])))?;
+        if s_param < 1 {
+            return Err(Error::Parameter("Kucherov's S parameter needs to be >= 1".to_owned()));
+        }
+        Ok(KucherovMode {s_param})
     }
 }
 
@@ -43,23 +46,28 @@ impl IsMode for KucherovMode {
         )
     }
 
-    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32) -> Vec<i32>{
+    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32, max_errors : Option<i32>) -> Vec<i32>{
         let mut ls : Vec<i32> = Vec::new();
         if patt_len < thresh{
             ls.push(patt_len);
             return ls;
         }
+        // errors permitted for an overlap of length l. a new block begins wherever this grows
+        let errors_at = |l : i32| {
+            let errors = (err_rate*(l as f32)).ceil() as i32;
+            match max_errors {
+                Some(max_errors) => min(errors, max_errors),
+                None => errors,
+            }
+        };
         for l in thresh..patt_len+1{
-            let f_len = l as f32;
-            if (err_rate*(f_len-1.0)).ceil()
-                < (err_rate*f_len).ceil() {
+            if errors_at(l-1) < errors_at(l) {
                 ls.push(l);
             }
         }
         ls.push(patt_len+1);
-        let k =
-            max(1, (err_rate*(ls[0] as f32)).ceil() as i32) //when err_rate is 0 there is no block to start with. avoid this niche case
-            + self.s_param - 1;
+        // the errors of the prefixes shorter than ls[0] plus S. (with a cap, errors_at(ls[0]) isn't always one more)
+        let k = errors_at(ls[0]-1) + self.s_param;
         let big_l : i32 = max(
             (((ls[0]-1) as f32)/(k as f32)).ceil() as i32,
             ls[0] - thresh,
@@ -1,12 +1,17 @@
 use std::fmt::{Display, Debug};
+use crate::error::Error;
+use crate::structs::run_config::Config;
 
 pub mod kucherov;
 pub mod valimaki;
+pub mod scheme;
+pub mod auto;
 pub type Mode = Box<dyn IsMode>;
 
 /*
 "interface" for new filtering and partition schemes.
 1. Create any struct that implements these functions
+   (block_order, searches, error_bounds, bidirectional and max_errors have defaults, for modes matching blocks forwards only)
 2. Add your new struct to the code in setup.rs so that the solver will use it when the arg is used
 */
 pub trait IsMode: Sync + Display + Debug {
@@ -19,8 +24,12 @@ pub trait IsMode: Sync + Display + Debug {
     */
     fn filter_func(&self, completed_blocks : i32, patt_blocks : i32, blind_blocks : i32) -> i32;
 
-    // partition scheme. For a pattern of given length and alg parameters, return a vector of block lengths. order will be respected
-    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32) -> Vec<i32>;
+    /*
+    partition scheme. For a pattern of given length and alg parameters, return a vector of block lengths. order will be respected
+    "max_errors" : if given, no overlap has more errors than this, however long it is (see Config::max_error_count).
+    the number of errors needn't grow with the length beyond it, so fewer (longer) blocks will do
+    */
+    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32, max_errors : Option<i32>) -> Vec<i32>;
 
     // return true IFF a node with the properties represented by the args should generate candidates
     fn candidate_condition(&self,generous_overlap_len : i32, completed_blocks : i32, thresh : i32, errors : i32 ) -> bool;
@@ -30,25 +39,117 @@ pub trait IsMode: Sync + Display + Debug {
 
     // Used by testing.rs for the cargo testing
     fn get_guaranteed_extra_blocks(&self) -> i32;
+
+    /*
+    search order. The blocks of the query search with index "search" (see searches()) for the filter with "blind_blocks"
+    (ids blind_blocks..patt_blocks), in the order they are matched. Each block after the first must be next to those before it,
+    extending the match FORWARDS (to a higher id) or BACKWARDS (to a lower id). "completed_blocks" (see filter_func) counts
+    the blocks in this order. Candidates are only generated once block blind_blocks is matched, so the search must reach it
+    before the end of any overlap it is to find.
+    By default the blocks are matched forwards from the first, as suffix filters are.
+    */
+    fn block_order(&self, _search : i32, patt_blocks : i32, blind_blocks : i32) -> Vec<i32> {
+        (blind_blocks..patt_blocks).collect()
+    }
+
+    // the number of query searches for each filter, each with its own block_order and error_bounds
+    fn searches(&self) -> i32 {
+        1
+    }
+
+    /*
+    the lower and upper bound of the errors of a node of the query search with index "search" (see searches()).
+    A node with fewer errors than the lower bound (or more than the upper) doesn't generate candidates or search further,
+    ie: an upper bound below 0 doesn't search the filter at all.
+    By default there is no lower bound, and the upper bound is that of filter_func
+    */
+    fn error_bounds(&self, _search : i32, completed_blocks : i32, patt_blocks : i32, blind_blocks : i32) -> (i32, i32) {
+        (0, self.filter_func(completed_blocks, patt_blocks, blind_blocks))
+    }
+
+    /*
+    return true IFF the mode needs the bidirectional index (see index::BidirectionalIndex). Only then are block_order,
+    searches and the lower bounds of error_bounds respected. Needed for any search extending backwards
+    */
+    fn bidirectional(&self) -> bool {
+        false
+    }
+
+    // if the mode is only guaranteed to find the overlaps with up to some number of errors, that number (see Config::max_error_count)
+    fn max_errors(&self) -> Option<i32> {
+        None
+    }
+
+    // why the mode can't find all overlaps of a run with the given config, if it can't (see check_mode)
+    fn check_config(&self, _config : &Config) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/*
+a Parameter error if the mode can't find all overlaps of a run with the given config.
+Modes with max_errors need runs limiting the errors to that, and each mode may have conditions of its own (see check_config).
+*/
+pub fn check_mode(mode : &dyn IsMode, config : &Config) -> Result<(), Error> {
+    if let Some(mode_errors) = mode.max_errors() {
+        if config.max_error_count().is_none_or(|errors| errors > mode_errors) {
+            return Err(Error::Parameter(format!("The {} mode only finds overlaps with up to {} errors. \
+                                                 Run with --max_errors limiting them to that.", mode, mode_errors)));
+        }
+    }
+    mode.check_config(config).map_err(|e| Error::Parameter(format!("The {} mode can't be used for this run. {}", mode, e)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forwards,
+    Backwards,
+}
+
+/*
+the direction each block of the given order (see IsMode::block_order) extends the match in. The first is matched forwards.
+panics if the order doesn't cover the blocks of the filter, or has a block that isn't next to the ones before it.
+*/
+pub fn block_directions(order : &[i32], patt_blocks : i32, blind_blocks : i32) -> Vec<Direction> {
+    assert_eq!(order.len() as i32, patt_blocks - blind_blocks, "block order {:?} doesn't cover the filter's blocks", order);
+    let mut directions = Vec::with_capacity(order.len());
+    let (mut lowest, mut highest) = (order[0], order[0]);
+    for &block in order.iter() {
+        if directions.is_empty() {
+            directions.push(Direction::Forwards);
+        } else if block == highest + 1 {
+            highest = block;
+            directions.push(Direction::Forwards);
+        } else if block == lowest - 1 {
+            lowest = block;
+            directions.push(Direction::Backwards);
+        } else {
+            panic!("block {} of order {:?} isn't next to the blocks before it", block, order);
+        }
+    }
+    assert!(lowest == blind_blocks && highest == patt_blocks - 1, "block order {:?} leaves the filter's blocks", order);
+    directions
 }
 /*
 Add your custom modes in this switch statement so that
 they will be used when the solver is run with the appropriate -m flag arg.
+(-m=auto isn't one of them. It is chosen from these once the input is indexed, see auto::select)
+Invalid names and mode arguments are Parameter errors.
 */
-pub fn get_mode(arg : &str) -> Mode {
+pub fn get_mode(arg : &str) -> Result<Mode, Error> {
     let tokens : Vec<&str> = arg.split('_').collect();
-    if tokens.len() == 0 {
-        panic!("")
-    }
     let mode_args = &tokens[1..];
-    match tokens[0] {
+    let mode : Mode = match tokens[0] {
         "valimaki" => Box::new(valimaki::ValimakiMode::new()),
-        "kucherov" => Box::new(kucherov::KucherovMode::new(mode_args)),
+        "kucherov" => Box::new(kucherov::KucherovMode::new(mode_args)?),
+        // the path of the scheme file may contain underscores itself
+        "scheme" => Box::new(scheme::SchemeMode::load(arg.split_once('_').map_or("", |(_, path)| path))?),
         /*
         NEW MODE OPTIONS GO IN THIS BLOCK
         CATCH the name you want it to be associated with, whatever you like.
         return a box contining your IsMode-implementing struct like this:
             your_mod_rs_file::YourStruct::new(mode_args)
+        (with a ? if your new() returns a Result, ie: rejecting the mode_args)
         ("IsMode" trait is defined above)
         You can also leave out the mode_args if your new() is defined as requiring no parameter.
         */
@@ -59,11 +160,12 @@ pub fn get_mode(arg : &str) -> Mode {
 
 
         // YOUR MODES GO HERE ^^^^
-        _ => panic!("No mode with the given name found!"),
-    }
+        _ => return Err(Error::Parameter(format!("No mode with the name '{}' found!", tokens[0]))),
+    };
+    Ok(mode)
 }
 
 
 pub fn default_mode() -> Mode {
-    Box::new(kucherov::KucherovMode::new(&vec!["2"]))
+    Box::new(kucherov::KucherovMode::new(&["2"]).expect("S=2 is valid"))
 }
@@ -0,0 +1,245 @@
+use std::cmp::min;
+use std::fs;
+use std::fmt;
+use serde::Deserialize;
+use crate::modes::IsMode;
+use crate::error::Error;
+use crate::structs::run_config::Config;
+
+/*
+A search scheme in the style of Kianfar et al. (2018), read from a file instead of being compiled in.
+The first symbols of each pattern, those every overlap of it covers, are divided into a number of PARTS.
+Each search of the scheme matches these parts in its own order, and after each part, the errors so far must lie within
+the search's lower and upper bound for it. The remainder of the pattern is one more block, matched last and forwards,
+as long as the errors stay within the last upper bound, which must be K. The scheme is for some number of errors K,
+and only finds all overlaps if none have more errors than that (--max_errors).
+
+The file is TOML, with the scheme's parts and errors and a [[search]] table per search, which has no other keys.
+Parts are numbered from the start of the pattern (the end that overlaps the other string), from 0.
+
+    parts = 3
+    errors = 2
+    [[search]]
+    order = [0, 1, 2]
+    lower = [0, 0, 0]
+    upper = [0, 2, 2]
+    ...
+
+The bounds are cumulative, ie: upper[i] bounds the errors in the first i+1 parts of the search's order.
+Loading checks that the scheme covers every distribution of up to K errors over the parts.
+The file only sets the searches: the parts always divide the first THRESH-K symbols as evenly as possible, and no other
+partition of the pattern is supported (see get_block_lengths).
+*/
+#[derive(Debug)]
+pub struct SchemeMode {
+    path : String,
+    parts : i32,
+    errors : i32,
+    searches : Vec<Search>,
+}
+
+// the contents of a scheme file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemeFile {
+    parts : i32,
+    errors : i32,
+    #[serde(default, rename = "search")]
+    searches : Vec<Search>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Search {
+    order : Vec<i32>,
+    lower : Vec<i32>,
+    upper : Vec<i32>,
+}
+
+impl SchemeMode {
+    pub fn load(path : &str) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::in_file(e, "read the search scheme", path))?;
+        SchemeMode::parse(path, &text)
+            .map_err(|e| Error::Parameter(format!("Invalid search scheme {:?}: {}", path, e)))
+    }
+
+    pub fn parse(path : &str, text : &str) -> Result<Self, String> {
+        let SchemeFile{parts, errors, searches} = toml::from_str(text).map_err(|e| e.to_string())?;
+        let scheme = SchemeMode {
+            path : path.to_owned(),
+            parts,
+            errors,
+            searches,
+        };
+        scheme.validate()?;
+        Ok(scheme)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.parts < 1 || self.errors < 0 {
+            return Err("there must be at least 1 part and at least 0 errors".to_owned());
+        }
+        if self.searches.is_empty() {
+            return Err("there are no searches".to_owned());
+        }
+        for (i, search) in self.searches.iter().enumerate() {
+            let mut sorted = search.order.clone();
+            sorted.sort();
+            if sorted != (0..self.parts).collect::<Vec<i32>>() {
+                return Err(format!("the order of search {} isn't one of the parts 0..{}", i, self.parts));
+            }
+            for (j, &part) in search.order.iter().enumerate().skip(1) {
+                let before = &search.order[..j];
+                let highest = *before.iter().max().unwrap();
+                let lowest = *before.iter().min().unwrap();
+                if part != highest + 1 && part != lowest - 1 {
+                    return Err(format!("part {} of search {} isn't next to the parts before it", part, i));
+                }
+            }
+            if search.lower.len() != search.order.len() || search.upper.len() != search.order.len() {
+                return Err(format!("search {} needs a lower and upper bound for each part", i));
+            }
+            if search.lower.iter().zip(search.upper.iter()).any(|(&l, &u)| l < 0 || l > u || u > self.errors)
+                    || search.lower.windows(2).any(|w| w[0] > w[1])
+                    || search.upper.windows(2).any(|w| w[0] > w[1]) {
+                return Err(format!("the bounds of search {} must be non-decreasing, with 0 <= lower <= upper <= errors", i));
+            }
+            // the rest of the pattern is matched within the last upper bound, and may hold any of the errors
+            if search.upper.last() != Some(&self.errors) {
+                return Err(format!("the last upper bound of search {} must be the errors of the scheme ({})", i, self.errors));
+            }
+        }
+        let mut distribution = vec![0; self.parts as usize];
+        match self.uncovered(&mut distribution, 0, self.errors) {
+            Some(uncovered) => Err(format!("no search covers {} errors in parts 0..{} respectively", format_distribution(&uncovered), self.parts)),
+            None => Ok(()),
+        }
+    }
+
+    // a distribution of errors over the parts (with those before "part" as given, and at most "remaining" more) no search covers
+    fn uncovered(&self, distribution : &mut [i32], part : usize, remaining : i32) -> Option<Vec<i32>> {
+        if part == distribution.len() {
+            return if self.searches.iter().any(|search| search.covers(distribution)) {None} else {Some(distribution.to_vec())};
+        }
+        for errors in 0..(remaining + 1) {
+            distribution[part] = errors;
+            if let Some(uncovered) = self.uncovered(distribution, part + 1, remaining - errors) {
+                return Some(uncovered);
+            }
+        }
+        distribution[part] = 0;
+        None
+    }
+}
+
+impl Search {
+    // whether a match with the given errors in each part stays within the bounds of this search
+    fn covers(&self, distribution : &[i32]) -> bool {
+        let mut errors = 0;
+        for (i, &part) in self.order.iter().enumerate() {
+            errors += distribution[part as usize];
+            if errors < self.lower[i] || errors > self.upper[i] {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn format_distribution(distribution : &[i32]) -> String {
+    distribution.iter().map(|e| e.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for SchemeMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scheme {} (K={}, {} parts, {} searches)", self.path, self.errors, self.parts, self.searches.len())
+    }
+}
+
+#[allow(unused_variables)]
+impl IsMode for SchemeMode {
+    fn get_guaranteed_extra_blocks(&self) -> i32 {
+        1
+    }
+
+    // only the filter of the whole pattern is searched (see error_bounds). Shorter patterns have too few blocks
+    fn get_fewest_suff_blocks(&self) -> i32 {
+        self.parts
+    }
+
+    fn filter_func(&self, completed_blocks : i32, patt_blocks : i32, blind_blocks : i32) -> i32 {
+        self.error_bounds(0, completed_blocks, patt_blocks, blind_blocks).1
+    }
+
+    /*
+    the parts divide the first thresh-K symbols of the pattern (as evenly as possible), as every overlap covers at least
+    these in the pattern: the longer overlap length reaches thresh and the two differ by an indel per error at most.
+    */
+    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32, max_errors : Option<i32>) -> Vec<i32> {
+        // runs without a limit (or with too few symbols for the parts) are rejected beforehand (see check_config)
+        let max_errors = max_errors.unwrap_or(self.errors);
+        let covered = thresh - max_errors;
+        if covered < self.parts || patt_len < covered {
+            return vec![patt_len];
+        }
+        let mut block_lengths : Vec<i32> = (0..self.parts)
+            .map(|part| covered / self.parts + if part < covered % self.parts {1} else {0})
+            .collect();
+        if patt_len > covered {
+            block_lengths.push(patt_len - covered);
+        }
+        block_lengths
+    }
+
+    fn candidate_condition(&self,
+            generous_overlap_len : i32,
+            completed_blocks : i32,
+            thresh : i32,
+            errors : i32
+            ) -> bool{
+        generous_overlap_len >= thresh && completed_blocks >= self.parts
+    }
+
+    fn block_order(&self, search : i32, patt_blocks : i32, blind_blocks : i32) -> Vec<i32> {
+        if blind_blocks > 0 {
+            // not searched at all
+            return (blind_blocks..patt_blocks).collect();
+        }
+        let mut order = self.searches[search as usize].order.clone();
+        order.extend(self.parts..patt_blocks);
+        order
+    }
+
+    fn searches(&self) -> i32 {
+        self.searches.len() as i32
+    }
+
+    fn error_bounds(&self, search : i32, completed_blocks : i32, patt_blocks : i32, blind_blocks : i32) -> (i32, i32) {
+        if blind_blocks > 0 {
+            return (0, -1);
+        }
+        let search = &self.searches[search as usize];
+        let last = (self.parts - 1) as usize;
+        let lower = if completed_blocks == 0 {0} else {search.lower[min(completed_blocks as usize - 1, last)]};
+        (lower, search.upper[min(completed_blocks as usize, last)])
+    }
+
+    fn bidirectional(&self) -> bool {
+        true
+    }
+
+    fn max_errors(&self) -> Option<i32> {
+        Some(self.errors)
+    }
+
+    // each part needs a symbol at least (see get_block_lengths)
+    fn check_config(&self, config : &Config) -> Result<(), String> {
+        let covered = config.thresh - config.max_error_count().unwrap_or(self.errors);
+        if covered < self.parts {
+            return Err(format!("Its {} parts divide the first THRESH-K symbols of each string, only {} with these arguments.",
+                               self.parts, covered));
+        }
+        Ok(())
+    }
+}
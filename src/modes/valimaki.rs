@@ -2,7 +2,7 @@ use std::cmp::{min};
 use crate::modes::IsMode;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ValimakiMode;
 
 impl ValimakiMode {
@@ -42,17 +42,21 @@ impl IsMode for ValimakiMode {
     }
 
     #[inline]
-    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32) -> Vec<i32>{
+    fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32, max_errors : Option<i32>) -> Vec<i32>{
         let mut ps : Vec<i32> = Vec::new();
         if patt_len < thresh{
             ps.push(patt_len);
             return ps;
         }
         for l in thresh..patt_len+1{
+            let errors = match max_errors {
+                Some(max_errors) => (err_rate * (l as f32)).ceil().min(max_errors as f32),
+                None => (err_rate * (l as f32)).ceil(),
+            };
             let one_p = (
                 (l as f32)
                     /
-                    ((err_rate * (l as f32)).ceil() + 1.0)
+                    (errors + 1.0)
             ).ceil() as i32;
             ps.push(one_p);
         }
@@ -0,0 +1,422 @@
+use crate::structs::solutions::{Solution, Cigar};
+use crate::structs::run_config::{Config, Maps, OutputFormat};
+use crate::useful::Orientation;
+
+use std::io::{self, Write, BufRead, BufReader};
+use std::fs::File;
+use std::cmp::{min, max, Ordering};
+
+/*
+Writing solutions in the output formats described in the README.
+Solutions are expected to already be in their EXTERNAL representation (as returned by the solver).
+*/
+
+pub fn write_header<W : Write>(buf : &mut W, config : &Config) -> io::Result<()> {
+    if config.cigar {
+        buf.write_all("idA\tidB\tO\tOHA\tOHB\tOLA\tOLB\tK\tCIGAR\n".as_bytes())
+    } else {
+        buf.write_all("idA\tidB\tO\tOHA\tOHB\tOLA\tOLB\tK\n".as_bytes())
+    }
+}
+
+/*
+writes whatever comes before the first solution.
+TSV: the header line only if requested (-f)
+GFA: always the header line and one S line per input string (the segments the edges refer to)
+PAF: nothing
+*/
+pub fn write_preamble<W : Write>(buf : &mut W, maps : &Maps, config : &Config) -> io::Result<()> {
+    match config.output_format {
+        OutputFormat::Tsv => {
+            if config.format_line {
+                write_header(buf, config)?;
+            }
+        },
+        OutputFormat::Gfa1 | OutputFormat::Gfa2 => {
+            write_gfa_segments(buf, maps, config)?;
+        },
+        OutputFormat::Paf => (),
+    }
+    Ok(())
+}
+
+/*
+writes a single solution to file.
+the written string won't be broken up
+*/
+#[inline]
+pub fn write_solution<W : Write>(buf : &mut W, s : &Solution, maps : &Maps, config : &Config) -> io::Result<()> {
+    let formatted = match config.output_format {
+        OutputFormat::Tsv => format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}{}\n",
+                                     maps.get_name_for(s.id_a),
+                                     maps.get_name_for(s.id_b),
+                                     s.orientation,
+                                     s.overhang_left_a,
+                                     s.overhang_right_b,
+                                     s.overlap_a,
+                                     s.overlap_b,
+                                     s.errors,
+                                     match s.cigar {
+                                         Some(ref cigar) => format!("\t{}", cigar_string(cigar)),
+                                         None => String::new(),
+                                     },
+        ),
+        OutputFormat::Gfa1 => gfa1_edge(s, maps, config),
+        OutputFormat::Gfa2 => gfa2_edge(s, maps, config),
+        OutputFormat::Paf => paf_line(s, maps, config),
+    };
+    buf.write_all(formatted.as_bytes())?;
+    if config.print{
+        print_solution(s, maps);
+    }
+    Ok(())
+}
+
+/*
+merges the given solutions into a previous TSV output file, writing the union of the two.
+Both must be sorted the same way (see solution_comparator), which a previous output written with -g is not.
+Lines of the previous output are copied as they are, and a solution that is already among them is not written again.
+Its header line (if any) is skipped, as the preamble of this output is written separately.
+returns the number of solutions written.
+*/
+pub fn write_merged<W : Write>(buf : &mut W, previous : &str, solutions : &[Solution],
+                               maps : &Maps, config : &Config) -> io::Result<usize> {
+    let reader = BufReader::new(File::open(previous)?);
+    let mut new = solutions.iter().peekable();
+    let mut last_line = String::new();
+    let mut written = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() || line.starts_with("idA\t") {
+            continue;
+        }
+        let key = match tsv_key(&line) {
+            Some(key) => key,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("Can't interpret line {:?} of previous output {}.", line, previous))),
+        };
+        if tsv_key(&last_line).is_some_and(|last_key| last_key > key) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("Previous output {} is not sorted (was it written with -g?).", previous)));
+        }
+        while let Some(s) = new.peek() {
+            match solution_key(s, maps).cmp(&key) {
+                Ordering::Less => {
+                    write_solution(buf, s, maps, config)?;
+                    written += 1;
+                },
+                Ordering::Equal => (),
+                Ordering::Greater => break,
+            }
+            new.next();
+        }
+        buf.write_all(line.as_bytes())?;
+        buf.write_all(b"\n")?;
+        written += 1;
+        last_line = line;
+    }
+    for s in new {
+        write_solution(buf, s, maps, config)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+type SolutionKey<'a> = (&'a str, &'a str, Orientation, i32, i32, usize, usize);
+
+// the fields solutions are sorted by, as in solution_comparator
+fn solution_key<'a>(s : &Solution, maps : &'a Maps) -> SolutionKey<'a> {
+    (maps.get_name_for(s.id_a), maps.get_name_for(s.id_b), s.orientation.clone(),
+     s.overhang_left_a, s.overhang_right_b, s.overlap_a, s.overlap_b)
+}
+
+// as solution_key, for a solution written as a TSV line
+fn tsv_key(line : &str) -> Option<SolutionKey<'_>> {
+    let fields : Vec<&str> = line.split('\t').collect();
+    if fields.len() < 8 {
+        return None;
+    }
+    let orientation = match fields[2] {
+        "N" => Orientation::Normal,
+        "I" => Orientation::Reversed,
+        _ => return None,
+    };
+    Some((fields[0], fields[1], orientation,
+          fields[3].parse().ok()?, fields[4].parse().ok()?, fields[5].parse().ok()?, fields[6].parse().ok()?))
+}
+
+/*
+The lengths and overlapping sections of both strings of a solution, as [start, end) intervals.
+A is always in its input orientation. The B interval is given for B as it is ORIENTED in the
+solution (ie: reverse complemented if the orientation is reversed). b_forward() converts it to
+coordinates in B as it appears in the input.
+
+OHA and OHB are the differences between the parts before and after the overlap:
+OHA == a1 - b1 and OHB == b3 - a3, where one of each pair is always 0
+*/
+pub struct OverlapSpans {
+    pub a_len : usize,
+    pub b_len : usize,
+    pub a_start : usize,
+    pub a_end : usize,
+    pub b_start : usize,
+    pub b_end : usize,
+}
+
+impl OverlapSpans {
+    pub fn of(s : &Solution, maps : &Maps) -> OverlapSpans {
+        let a_start = max(0, s.overhang_left_a) as usize;
+        let b_start = max(0, -s.overhang_left_a) as usize;
+        OverlapSpans {
+            a_len : maps.get_length(s.id_a),
+            b_len : maps.get_length(s.id_b),
+            a_start,
+            a_end : a_start + s.overlap_a,
+            b_start,
+            b_end : b_start + s.overlap_b,
+        }
+    }
+
+    // the B interval in the input orientation of B
+    pub fn b_forward(&self, orientation : &Orientation) -> (usize, usize) {
+        match *orientation {
+            Orientation::Normal => (self.b_start, self.b_end),
+            Orientation::Reversed => (self.b_len - self.b_end, self.b_len - self.b_start),
+        }
+    }
+}
+
+/*
+How the two strings of a solution are laid out relative to one another.
+Solutions with both overhangs 0 (identical extents) count as A containing B.
+*/
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Layout {
+    AThenB,
+    BThenA,
+    AContainsB,
+    BContainsA,
+}
+
+pub fn layout(s : &Solution) -> Layout {
+    match (s.overhang_left_a, s.overhang_right_b) {
+        (oha, ohb) if oha >= 0 && ohb <= 0 => Layout::AContainsB,
+        (oha, ohb) if oha <= 0 && ohb >= 0 => Layout::BContainsA,
+        (oha, _) if oha > 0 => Layout::AThenB,
+        _ => Layout::BThenA,
+    }
+}
+
+pub fn cigar_string(cigar : &Cigar) -> String {
+    cigar.iter().map(|&(len, op)| format!("{}{}", len, op as char)).collect()
+}
+
+/*
+The alignment of a solution with either A or B as the reference (the first string of the edge),
+reversed if the edge has both strings in the opposite orientation to the solution.
+Unless the alignment was computed (-c), the solver only knows the lengths of the overlapping sections,
+so these are described as CIGAR-style lengths instead (see length_cigar).
+*/
+fn edge_cigar(s : &Solution, a_is_reference : bool, reversed : bool) -> String {
+    match s.cigar {
+        Some(ref cigar) => {
+            let mut ops : Cigar = cigar.iter().map(|&(len, op)| {
+                match op {
+                    b'I' if !a_is_reference => (len, b'D'),
+                    b'D' if !a_is_reference => (len, b'I'),
+                    _ => (len, op),
+                }
+            }).collect();
+            if reversed {
+                ops.reverse();
+            }
+            cigar_string(&ops)
+        },
+        None if a_is_reference => length_cigar(s.overlap_a, s.overlap_b),
+        None => length_cigar(s.overlap_b, s.overlap_a),
+    }
+}
+
+/*
+the number of edits (substitutions, insertions and deletions) of a solution, as NM tags count them.
+K weighs each kind by its cost (see EditCosts), so only with unit costs (or substitutions only) are they the same.
+Otherwise the indels are counted in the alignment, and the rest of K is substitutions. Without one (-c) it isn't known.
+*/
+fn edit_count(s : &Solution, config : &Config) -> Option<u32> {
+    let costs = &config.costs;
+    match s.cigar {
+        Some(ref cigar) => {
+            let count_of = |kind : u8| cigar.iter().filter(|&&(_, op)| op == kind).map(|&(len, _)| len as u32).sum::<u32>();
+            let (insertions, deletions) = (count_of(b'I'), count_of(b'D'));
+            let substitutions = (s.errors - insertions * costs.insertion - deletions * costs.deletion) / costs.substitution;
+            Some(substitutions + insertions + deletions)
+        },
+        None if !config.edit_distance => Some(s.errors / costs.substitution),
+        None if costs.is_unit() => Some(s.errors),
+        None => None,
+    }
+}
+
+// the NM:i tag of a GFA edge or PAF line, unless the number of edits isn't known
+fn nm_tag(s : &Solution, config : &Config) -> String {
+    match edit_count(s, config) {
+        Some(edits) => format!("\tNM:i:{}", edits),
+        None => String::new(),
+    }
+}
+
+/*
+matching for the length of the shorter section, with the difference as a deletion (D) or insertion (I).
+*/
+fn length_cigar(ref_len : usize, query_len : usize) -> String {
+    let common = min(ref_len, query_len);
+    if ref_len > query_len {
+        format!("{}M{}D", common, ref_len - query_len)
+    } else if query_len > ref_len {
+        format!("{}M{}I", common, query_len - ref_len)
+    } else {
+        format!("{}M", common)
+    }
+}
+
+// GFA names are the input names, each input string being a segment (in both orientations)
+// the same symbols are the strand in PAF
+fn gfa_orientation(orientation : &Orientation) -> char {
+    match *orientation {
+        Orientation::Normal => '+',
+        Orientation::Reversed => '-',
+    }
+}
+
+fn write_gfa_segments<W : Write>(buf : &mut W, maps : &Maps, config : &Config) -> io::Result<()> {
+    let gfa2 = config.output_format == OutputFormat::Gfa2;
+    buf.write_all(if gfa2 {b"H\tVN:Z:2.0\n"} else {b"H\tVN:Z:1.0\n"})?;
+    // with reversals, only the even ids are strings in their input orientation
+    let step = if config.reversals {2} else {1};
+    for id in (0..maps.num_ids()).step_by(step) {
+        let sequence = if config.gfa_sequences {
+            let mut forwards = maps.get_string(id).to_vec();
+            forwards.reverse(); // the text is backwards
+            String::from_utf8(forwards).unwrap()
+        } else {
+            "*".to_owned()
+        };
+        let line = if gfa2 {
+            format!("S\t{}\t{}\t{}\n", maps.get_name_for(id), maps.get_length(id), sequence)
+        } else {
+            format!("S\t{}\t{}\tLN:i:{}\n", maps.get_name_for(id), sequence, maps.get_length(id))
+        };
+        buf.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+/*
+dovetails become L lines from the string on the left to the string on the right.
+containments become C lines, always with the container in + orientation
+(so the position of the contained string is in the container as it appears in the input)
+*/
+fn gfa1_edge(s : &Solution, maps : &Maps, config : &Config) -> String {
+    let a_name = maps.get_name_for(s.id_a);
+    let b_name = maps.get_name_for(s.id_b);
+    let b_or = gfa_orientation(&s.orientation);
+    let spans = OverlapSpans::of(s, maps);
+    let nm = nm_tag(s, config);
+    match layout(s) {
+        Layout::AThenB => format!("L\t{}\t+\t{}\t{}\t{}{}\n",
+                                  a_name, b_name, b_or, edge_cigar(s, true, false), nm),
+        Layout::BThenA => format!("L\t{}\t{}\t{}\t+\t{}{}\n",
+                                  b_name, b_or, a_name, edge_cigar(s, false, false), nm),
+        Layout::AContainsB => format!("C\t{}\t+\t{}\t{}\t{}\t{}{}\n",
+                                      a_name, b_name, b_or, spans.a_start,
+                                      edge_cigar(s, true, false), nm),
+        Layout::BContainsA => {
+            // A lies in the oriented B. seen from B in its input orientation, A is reversed IFF B was
+            let (b_forward_start, _) = spans.b_forward(&s.orientation);
+            format!("C\t{}\t+\t{}\t{}\t{}\t{}{}\n",
+                    b_name, a_name, b_or, b_forward_start,
+                    edge_cigar(s, false, b_or == '-'), nm)
+        },
+    }
+}
+
+/*
+every solution becomes an E line. Positions are on the input orientation of each segment,
+with '$' marking positions at the end of a segment as GFA2 requires.
+*/
+fn gfa2_edge(s : &Solution, maps : &Maps, config : &Config) -> String {
+    let spans = OverlapSpans::of(s, maps);
+    let (b_start, b_end) = spans.b_forward(&s.orientation);
+    format!("E\t*\t{}+\t{}{}\t{}\t{}\t{}\t{}\t{}{}\n",
+            maps.get_name_for(s.id_a),
+            maps.get_name_for(s.id_b),
+            gfa_orientation(&s.orientation),
+            gfa2_position(spans.a_start, spans.a_len),
+            gfa2_position(spans.a_end, spans.a_len),
+            gfa2_position(b_start, spans.b_len),
+            gfa2_position(b_end, spans.b_len),
+            edge_cigar(s, true, false),
+            nm_tag(s, config),
+    )
+}
+
+fn gfa2_position(pos : usize, len : usize) -> String {
+    if pos == len {
+        format!("{}$", pos)
+    } else {
+        format!("{}", pos)
+    }
+}
+
+/*
+A is the query and B the target. As in PAF the target interval is on B's input orientation,
+with the strand telling whether the query maps to B or its reverse complement.
+The alignment (cg:Z, with -c) has B as the reference, on B's input orientation too.
+Without it the block length is derived from the overlap lengths,
+which is exact for Hamming distance but a lower bound for edit distance.
+The number of matching symbols is the block length minus the edits (NM:i, see edit_count),
+or minus K when the number of edits isn't known (and the tag is left out). The mapping quality is 255 (missing).
+*/
+fn paf_line(s : &Solution, maps : &Maps, config : &Config) -> String {
+    let spans = OverlapSpans::of(s, maps);
+    let (b_start, b_end) = spans.b_forward(&s.orientation);
+    let block_len = match s.cigar {
+        Some(ref cigar) => cigar.iter().map(|&(len, _)| len).sum(),
+        None => max(s.overlap_a, s.overlap_b),
+    };
+    let edits = edit_count(s, config);
+    let matches = block_len.saturating_sub(edits.unwrap_or(s.errors) as usize);
+    format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t255{}{}\n",
+            maps.get_name_for(s.id_a),
+            spans.a_len,
+            spans.a_start,
+            spans.a_end,
+            gfa_orientation(&s.orientation),
+            maps.get_name_for(s.id_b),
+            spans.b_len,
+            b_start,
+            b_end,
+            matches,
+            block_len,
+            nm_tag(s, config),
+            match s.cigar {
+                Some(_) => format!("\tcg:Z:{}", edge_cigar(s, false, s.orientation == Orientation::Reversed)),
+                None => String::new(),
+            },
+    )
+}
+
+// prints a rough visualization of the overlap to stdout (mostly for debugging purposes)
+fn print_solution(s : &Solution, maps : &Maps){
+    let a = &String::from_utf8_lossy(maps.get_string(s.id_a));
+    let b = &String::from_utf8_lossy(maps.get_string(s.id_b));
+    let a_name = maps.get_name_for(s.id_a);
+    let b_name = maps.get_name_for(s.id_b);
+    if s.overhang_left_a > 0{
+        let space = &" ".repeat(s.overhang_left_a as usize);
+        println!(" '{}':\t{}\n '{}':\t{}{}\n", a_name, a, b_name, space, b);
+    }else{
+        let space = &" ".repeat((-s.overhang_left_a) as usize);
+        println!(" '{}':\t{}{}\n '{}':\t{}\n", a_name, space, a, b_name, b);
+    }
+}
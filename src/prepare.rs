@@ -1,92 +1,353 @@
-use crate::structs::run_config::{Config, Maps};
+use crate::structs::run_config::{Config, Maps, Normalisation, Alphabet};
+use crate::error::Error;
 
-use bio::io::fasta;
+use bio::io::{fasta, fastq};
 use bidir_map::BidirMap;
+use flate2::read::MultiGzDecoder;
 
 use std::io;
+use std::io::{BufRead, BufReader, Read};
 use std::fs::File;
+use std::path::Path;
+
+// phred scores in FASTQ quality strings are offset by this value (Sanger / Illumina 1.8+)
+static PHRED_OFFSET : u8 = 33;
+
+// input path that makes the solver read from stdin instead of a file
+pub static STDIN_PATH : &str = "-";
+
+static GZIP_MAGIC : &[u8] = &[0x1f, 0x8b];
+static ZSTD_MAGIC : &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InputFormat{
+    Fasta,
+    Fastq,
+}
+
+// bgzip output is a series of gzip members, so it is read the same way as gzip
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Compression{
+    Plain,
+    Gzip,
+    Zstd,
+}
 
 
 /*
-builds the maps data structure from a fasta file + config
+builds the maps data structure from a fasta (or fastq) file + config
 the "maps" contains most of the constant information for the run
 > mappings from internal to external representations of strings ie: id-->"name"
 > mappings between internal represenations ie: id<-->index(in text)
 > important data ie: text
 > some convenient functions ie: get &str (in the text)
 */
-pub fn read_and_prepare(filename : &str, config : &Config) -> Result<Maps, io::Error> {
-    let mut text : Vec<u8> = Vec::new();
-    let mut id2name_vec : Vec<String> = Vec::new();
-    let mut id2index_bdmap : BidirMap<usize, usize> = BidirMap::new();
-
-    let f = File::open(filename)
-        .expect(&format!("Failed to open input file at {:?}\n", filename));
-    let reader = fasta::Reader::new(f);
-    let mut n_symbols_removed = 0;
-    for record in reader.records() {
-        let record = record?;
-        if let Some(name) = record.id(){
-            let id = id2name_vec.len();
-            let name = name.to_owned();
-            let mut str_vec = record.seq().to_vec();
-            if !config.n_alphabet{
-                let before_len = str_vec.len();
-                str_vec.retain(|c|*c != ('N' as u8));
-                if str_vec.len() < before_len{
-                    n_symbols_removed += before_len - str_vec.len();
+pub fn read_and_prepare(filename : &str, config : &Config) -> Result<Maps, Error> {
+    let mut buf_reader = open_input(filename, config)?;
+    let format = detect_format(&mut buf_reader, filename)?;
+    if config.verbosity >= 2 {
+        println!("OK input detected as {:?}.", format);
+    }
+    let mut builder = MapsBuilder::new();
+    match format {
+        InputFormat::Fasta => {
+            for record in fasta::Reader::from_bufread(buf_reader).records() {
+                let record = record?;
+                let name = record.id();
+                if !name.is_empty() {
+                    builder.add_record(name.to_owned(), record.seq().to_vec(), config)?;
                 }
             }
-            str_vec.reverse();
-            text.push('$' as u8);
-            let index = text.len();
-            id2index_bdmap.insert(id, index);
-            text.extend(str_vec.clone());
-            id2name_vec.push(name.clone());
-
-            if config.reversals{
-                let id = id2name_vec.len();
-                str_vec.reverse();
-                for i in 0..str_vec.len(){
-                    str_vec[i] = complement_u8(str_vec[i]);
+        },
+        InputFormat::Fastq => {
+            for record in fastq::Reader::from_bufread(buf_reader).records() {
+                let record = record?;
+                if let Err(e) = record.check() {
+                    return Err(Error::Input(format!("Bad FASTQ record {:?}: {}", record.id(), e)));
+                }
+                let name = record.id();
+                if !name.is_empty() {
+                    let mut str_vec = record.seq().to_vec();
+                    if let (Some(min_quality), Some(wildcard)) = (config.min_quality, config.alphabet.wildcard) {
+                        builder.n_symbols_masked += mask_low_quality(&mut str_vec, record.qual(), min_quality, wildcard)?;
+                    }
+                    builder.add_record(name.to_owned(), str_vec, config)?;
                 }
-                text.push('$' as u8);
-                let index = text.len();
-                id2index_bdmap.insert(id, index);
-                text.extend(str_vec);
-                id2name_vec.push(name);
             }
-        }
-    }
-    if n_symbols_removed > 0 {
-        println!("    WARNING\n\tOmitted {} N symbols found in input data.\n\t\
-        Run without flag --no_n to use these N strings intact.", n_symbols_removed);
+        },
     }
+    Ok(builder.finish(config))
+}
+
+/*
+reads the strings of a second file and appends them to maps prepared from a reference set
+as the queries (see Maps::query_ids and append_queries).
+*/
+pub fn read_and_append_queries(maps : Maps, filename : &str, config : &Config) -> Result<Maps, Error> {
+    let queries = read_and_prepare(filename, config)?;
+    append_queries(maps, queries, config)
+}
 
-    text.push('#' as u8);
-    text.shrink_to_fit();
-    id2name_vec.shrink_to_fit();
+/*
+appends the strings of the queries' maps to those of the reference set, keeping their order.
+An index built for the reference maps remains valid: the references keep their ids and positions in the text,
+and by default only they are indexed (see Maps::reference_ids).
+A Parameter error if either maps has queries already, and an Input error if the reference text isn't
+terminated as read_and_prepare leaves it.
+*/
+pub fn append_queries(maps : Maps, queries : Maps, config : &Config) -> Result<Maps, Error> {
+    if maps.first_query_id != 0 || queries.first_query_id != 0 {
+        return Err(Error::Parameter("Queries can only be appended once.".to_owned()));
+    }
+    let Maps{mut text, mut id2name_vec, mut id2index_bdmap, ..} = maps;
+    let first_query_id = id2name_vec.len();
+    if text.pop() != Some(b'#') {
+        return Err(Error::Input("The text of the reference strings isn't terminated by '#'.".to_owned()));
+    }
+    let shift = text.len();
+    for id in 0..queries.num_ids() {
+        id2index_bdmap.insert(first_query_id + id, queries.index_for(id) + shift);
+    }
+    text.extend_from_slice(&queries.text);
+    id2name_vec.extend(queries.id2name_vec);
+    if config.verbosity >= 2 {
+        println!("OK appended {} query strings to {} reference strings.", id2name_vec.len() - first_query_id, first_query_id);
+    }
 
-    let mut indexes : Vec<usize> = id2index_bdmap.second_col().map(|x| *x).collect();
+    let mut indexes : Vec<usize> = id2index_bdmap.second_col().cloned().collect();
     indexes.sort();
-    indexes.shrink_to_fit();
+    Ok(Maps{
+        text,
+        id2name_vec,
+        id2index_bdmap,
+        indexes,
+        first_query_id,
+    })
+}
 
-    let maps = Maps{
-        text : text,
-        id2name_vec : id2name_vec,
-        id2index_bdmap : id2index_bdmap,
-        indexes : indexes,
+/*
+opens the input file for reading, or stdin if the path is "-".
+compressed input is recognised by its magic bytes (not the extension) and decompressed as a stream,
+so no uncompressed copy of the input is ever needed.
+*/
+pub fn open_input(filename : &str, config : &Config) -> Result<Box<dyn BufRead>, Error> {
+    let raw : Box<dyn Read> = if filename == STDIN_PATH {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(filename)
+            .map_err(|e| Error::in_file(e, "open input file", filename))?)
     };
-    Ok(maps)
+    let mut buf_reader = BufReader::new(raw);
+    let compression = detect_compression(&mut buf_reader)?;
+    if config.verbosity >= 2 {
+        println!("OK input compression detected as {:?}.", compression);
+    }
+    let decompressed : Box<dyn BufRead> = match compression {
+        Compression::Plain => Box::new(buf_reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(buf_reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(buf_reader)?)),
+    };
+    Ok(decompressed)
 }
 
-fn complement_u8(x : u8) -> u8 {
-    match x{
-        b'A' => b'T',
-        b'C' => b'G',
-        b'G' => b'C',
-        b'T' => b'A',
-        b'N' => b'N',
-        _ => panic!("Bad string char '{}'", x as char),
+// The reader is only peeked, nothing is consumed.
+pub fn detect_compression<R : BufRead>(reader : &mut R) -> Result<Compression, io::Error> {
+    let peeked = reader.fill_buf()?;
+    if peeked.starts_with(GZIP_MAGIC) {
+        Ok(Compression::Gzip)
+    } else if peeked.starts_with(ZSTD_MAGIC) {
+        Ok(Compression::Zstd)
+    } else {
+        Ok(Compression::Plain)
+    }
+}
+
+/*
+FASTA records start with '>' and FASTQ records with '@'.
+The first byte (after decompression) decides. Only if the file doesn't tell (ie: it is empty)
+does the extension decide, looking past any compression extension ie: reads.fq.gz
+The reader is only peeked, nothing is consumed.
+*/
+pub fn detect_format<R : BufRead>(reader : &mut R, filename : &str) -> Result<InputFormat, io::Error> {
+    let first_byte = reader.fill_buf()?.first().cloned();
+    match first_byte {
+        Some(b'>') => Ok(InputFormat::Fasta),
+        Some(b'@') => Ok(InputFormat::Fastq),
+        _ => {
+            let mut path = Path::new(filename);
+            if let Some(stem) = path.extension()
+                    .filter(|e| ["gz", "bgz", "zst"].iter().any(|c| e.eq_ignore_ascii_case(c)))
+                    .and_then(|_| path.file_stem()) {
+                path = Path::new(stem);
+            }
+            let extension = path.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase());
+            match extension.as_deref() {
+                Some("fq") | Some("fastq") => Ok(InputFormat::Fastq),
+                _ => Ok(InputFormat::Fasta),
+            }
+        }
+    }
+}
+
+/*
+replaces every symbol with a phred quality below min_quality with the wildcard (see Alphabet),
+which the search and verification steps always consider a mismatch.
+returns the number of symbols masked, or an Input error if there isn't a quality for every symbol.
+*/
+pub fn mask_low_quality(seq : &mut [u8], qual : &[u8], min_quality : u8, wildcard : u8) -> Result<usize, Error> {
+    if seq.len() != qual.len() {
+        return Err(Error::Input(format!("Found {} quality scores for a string of {} symbols.", qual.len(), seq.len())));
+    }
+    let mut masked = 0;
+    for (c, &q) in seq.iter_mut().zip(qual.iter()) {
+        if q.saturating_sub(PHRED_OFFSET) < min_quality && *c != wildcard {
+            *c = wildcard;
+            masked += 1;
+        }
+    }
+    Ok(masked)
+}
+
+/*
+builds the maps data structure from reads that are already in memory (name, sequence)
+for users of the library that don't want to go through a fasta file
+*/
+pub fn prepare_reads<I, S>(reads : I, config : &Config) -> Result<Maps, Error>
+        where I : IntoIterator<Item=(S, Vec<u8>)>, S : Into<String> {
+    let mut builder = MapsBuilder::new();
+    for (name, seq) in reads {
+        builder.add_record(name.into(), seq, config)?;
+    }
+    Ok(builder.finish(config))
+}
+
+/*
+brings the symbols of one record into the alphabet of the index according to the normalisation policy.
+> lowercase (soft-masked) symbols are uppercased unless the policy is strict (or they are in the alphabet)
+> ambiguity codes (ie: IUPAC codes for nucleotides) become the wildcard if the policy is mask
+anything else that is still not in the alphabet rejects the whole input with an error naming the record.
+returns the number of (uppercased, masked) symbols.
+*/
+pub fn normalise(name : &str, seq : &mut [u8], policy : Normalisation, alphabet : &Alphabet) -> Result<(usize, usize), Error> {
+    let mut uppercased = 0;
+    let mut masked = 0;
+    for (pos, c) in seq.iter_mut().enumerate() {
+        if policy != Normalisation::Strict && c.is_ascii_lowercase() && !alphabet.contains(*c) {
+            c.make_ascii_uppercase();
+            uppercased += 1;
+        }
+        if let (Normalisation::Mask, Some(wildcard)) = (policy, alphabet.wildcard) {
+            if alphabet.ambiguous.contains(c) {
+                *c = wildcard;
+                masked += 1;
+            }
+        }
+        if !alphabet.contains(*c) {
+            return Err(Error::Input(
+                    format!("Record '{}' contains symbol '{}' at position {}, which is not in the {} alphabet. \
+                    Use --normalise to uppercase or mask unexpected symbols.", name, *c as char, pos, alphabet.name)));
+        }
+    }
+    Ok((uppercased, masked))
+}
+
+/*
+accumulates the text and mappings one input record at a time.
+each record is appended to the text backwards, preceded by a '$' (and its reversal right after if enabled)
+*/
+struct MapsBuilder {
+    text : Vec<u8>,
+    id2name_vec : Vec<String>,
+    id2index_bdmap : BidirMap<usize, usize>,
+    n_symbols_removed : usize,
+    n_symbols_masked : usize,
+    n_symbols_uppercased : usize,
+    n_symbols_iupac : usize,
+}
+
+impl MapsBuilder {
+    fn new() -> Self {
+        MapsBuilder {
+            text : Vec::new(),
+            id2name_vec : Vec::new(),
+            id2index_bdmap : BidirMap::new(),
+            n_symbols_removed : 0,
+            n_symbols_masked : 0,
+            n_symbols_uppercased : 0,
+            n_symbols_iupac : 0,
+        }
+    }
+
+    fn add_record(&mut self, name : String, mut str_vec : Vec<u8>, config : &Config) -> Result<(), Error> {
+        let id = self.id2name_vec.len();
+        let (uppercased, iupac) = normalise(&name, &mut str_vec, config.normalisation, &config.alphabet)?;
+        self.n_symbols_uppercased += uppercased;
+        self.n_symbols_iupac += iupac;
+        if !config.n_alphabet{
+            let before_len = str_vec.len();
+            str_vec.retain(|c| !config.alphabet.is_wildcard(*c));
+            if str_vec.len() < before_len{
+                self.n_symbols_removed += before_len - str_vec.len();
+            }
+        }
+        str_vec.reverse();
+        self.text.push(b'$');
+        let index = self.text.len();
+        self.id2index_bdmap.insert(id, index);
+        self.text.extend(str_vec.clone());
+        self.id2name_vec.push(name.clone());
+
+        if config.reversals{
+            let id = self.id2name_vec.len();
+            str_vec.reverse();
+            for c in str_vec.iter_mut(){
+                *c = config.alphabet.complement(*c);
+            }
+            self.text.push(b'$');
+            let index = self.text.len();
+            self.id2index_bdmap.insert(id, index);
+            self.text.extend(str_vec);
+            self.id2name_vec.push(name);
+        }
+        Ok(())
+    }
+
+    fn finish(self, config : &Config) -> Maps {
+        let MapsBuilder{mut text, mut id2name_vec, id2index_bdmap, n_symbols_removed,
+            n_symbols_masked, n_symbols_uppercased, n_symbols_iupac} = self;
+        if n_symbols_uppercased > 0 {
+            println!("    WARNING\n\tConverted {} lowercase symbols found in input data to uppercase.\n\t\
+            Run with --normalise=strict to reject them instead.", n_symbols_uppercased);
+        }
+        let wildcard = config.alphabet.wildcard.unwrap_or(b'N') as char;
+        if n_symbols_iupac > 0 {
+            println!("    WARNING\n\tReplaced {} ambiguity symbols found in input data with {}.", n_symbols_iupac, wildcard);
+        }
+        if n_symbols_removed > 0 {
+            println!("    WARNING\n\tOmitted {} {} symbols found in input data.\n\t\
+            Run without flag --no_n to use these {} strings intact.", n_symbols_removed, wildcard, wildcard);
+        }
+        if n_symbols_masked > 0 && config.verbosity >= 1 {
+            println!("OK masked {} symbols with quality below {} as {}.",
+                     n_symbols_masked, config.min_quality.unwrap_or(0), wildcard);
+        }
+
+        text.push(b'#');
+        text.shrink_to_fit();
+        id2name_vec.shrink_to_fit();
+
+        let mut indexes : Vec<usize> = id2index_bdmap.second_col().cloned().collect();
+        indexes.sort();
+        indexes.shrink_to_fit();
+
+        Maps{
+            text,
+            id2name_vec,
+            id2index_bdmap,
+            indexes,
+            first_query_id : 0,
+        }
     }
 }
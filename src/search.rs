@@ -1,10 +1,10 @@
 use crate::structs::run_config::{Config, Maps};
 use crate::structs::solutions::{Candidate};
 use crate::useful::companion_id;
-use crate::modes::Mode;
+use crate::modes::{Mode, Direction, block_directions};
+use crate::index::{Index, BidirectionalIndex, BiInterval};
 
 use bio::data_structures::fmindex::Interval;
-use bio::data_structures::suffix_array::RawSuffixArray;
 use bio::data_structures::fmindex::FMIndexable;
 
 // extern crate stacker; // for avoiding stackoverflow
@@ -14,8 +14,6 @@ use std::collections::HashSet;
 use std::cmp::{min,max};
 
 
-pub static READ_ERR : u8 = b'N';
-
 /*
 This is the meat and potatoes of this program, the candidate generation step (AKA search step).
 Given a pattern string (and some other information) and a config struct,
@@ -33,21 +31,29 @@ For this reason, much of the search, iteration etc. are all backwards
 Relative to the actual strings inside the text, and conceptually, the search is still forwards.
 These two equally-correct perspectives cannot be resolved in all cases, so when possible I use
 terms that are general in both directions. ie: FILTER instead of SUFFIX FILTER
+
+Modes that match the blocks of a filter in another order (see IsMode::block_order) extend the match
+in both directions, which is done over a bidirectional index instead (see recurse_bidirectional).
 */
 pub trait GeneratesCandidates : FMIndexable {
 
+    #[allow(clippy::too_many_arguments)]
     fn generate_candidates(&self,
                            pattern : &[u8],
                            config : &Config,
                            maps : &Maps,
                            id_a : usize,
-                           sa : &RawSuffixArray,
+                           index : &Index,
                            mode : &Mode,
+                           references_searched : bool,
+                           bidirectional : Option<&BidirectionalIndex>,
                             ) -> HashSet<Candidate> {
 
+        assert!(bidirectional.is_some() || !mode.bidirectional(), "{} mode needs a bidirectional index", mode);
+        assert!(bidirectional.is_some() || mode.searches() == 1, "{} mode has several searches per filter, which need a bidirectional index", mode);
         let mut candidate_set: HashSet<Candidate> = HashSet::new();
         let patt_len = pattern.len();
-        let block_lengths = mode.get_block_lengths(patt_len as i32, config.err_rate, config.thresh);
+        let block_lengths = mode.get_block_lengths(patt_len as i32, config.err_rate, config.thresh, config.max_error_count());
         assert_eq!(patt_len as i32, block_lengths.iter().sum());
         let block_id_lookup = get_block_id_lookup(&block_lengths);
         let full_interval = Interval {
@@ -65,20 +71,21 @@ pub trait GeneratesCandidates : FMIndexable {
         };
 
 
-        if max_b_len < config.thresh as usize{
+        if max_b_len < config.thresh as usize || (config.symmetric_thresh && patt_len < config.thresh as usize){
             //pattern too short
             return candidate_set;
         }
         let p_cns = PatternConstants{
-            pattern: pattern,
-            hard_error_cap : (max_b_len as f32 * config.err_rate).floor() as i32,
-            config : config,
-            maps : maps,
+            pattern,
+            cost_limit : config.error_limit(max_b_len),
+            config,
+            maps,
             block_id_lookup : &block_id_lookup,
-            sa : sa,
-            id_a : id_a,
-            patt_blocks : patt_blocks,
-            mode : mode,
+            index,
+            id_a,
+            patt_blocks,
+            mode,
+            references_searched,
         };
 
         /*
@@ -102,11 +109,25 @@ pub trait GeneratesCandidates : FMIndexable {
             };
 
             //This begins the search and represents a single "query" for a single pattern filter
-            self.recurse_candidates(
-                &mut candidate_set, &p_cns, &s_cns, 0, p_i,
-                LastOperation::Initial, 0, 0,
-                &full_interval,
-            );
+            match bidirectional {
+                Some(bi) => for search in 0..mode.searches() {
+                    let f_cns = FilterConstants::new(mode, search, &block_lengths, patt_len, first_block_id as i32);
+                    let first_right = f_cns.bounds[0].1;
+                    // only the right end of the filter may be an end of the overlap. elsewhere the search may begin with an indel
+                    let first_operation = if first_right + 1 == f_cns.right_end {LastOperation::Initial} else {LastOperation::Substitution};
+                    recurse_bidirectional(
+                        bi, &mut candidate_set, &p_cns, &s_cns, &f_cns, 0, 0,
+                        first_right, first_right + 1, 0,
+                        first_operation, LastOperation::Initial, 0, 0,
+                        &bi.full_interval(),
+                    );
+                },
+                None => self.recurse_candidates(
+                    &mut candidate_set, &p_cns, &s_cns, 0, 0, p_i,
+                    LastOperation::Initial, 0, 0,
+                    &full_interval,
+                ),
+            }
 
             // the filters begin as the entire pattern, and gradually get shorter.
             p_i -= *block_len;
@@ -115,16 +136,110 @@ pub trait GeneratesCandidates : FMIndexable {
         candidate_set
     }
 
+    /*
+    The candidate generation for when no errors are allowed at all (see Config::is_exact).
+    No filters are needed: the pattern is matched symbol by symbol (backwards, as above) exactly once,
+    and each B string preceded by '$' along the way overlaps the pattern suffix matched so far without errors.
+    These candidates need no verification (see verification::accept_all).
+    */
+    #[allow(clippy::too_many_arguments)]
+    fn generate_exact_candidates(&self,
+                                 pattern : &[u8],
+                                 config : &Config,
+                                 maps : &Maps,
+                                 id_a : usize,
+                                 index : &Index,
+                                 mode : &Mode,
+                                 references_searched : bool,
+                                 ) -> HashSet<Candidate> {
+
+        let mut candidate_set: HashSet<Candidate> = HashSet::new();
+        let patt_len = pattern.len();
+        if patt_len < config.thresh as usize {
+            //pattern too short
+            return candidate_set;
+        }
+        let block_id_lookup = vec![0; patt_len];
+        let p_cns = PatternConstants{
+            pattern,
+            cost_limit : 0,
+            config,
+            maps,
+            block_id_lookup : &block_id_lookup,
+            index,
+            id_a,
+            patt_blocks : 1,
+            mode,
+            references_searched,
+        };
+        // the whole pattern is the one and only filter
+        let s_cns = SuffixConstants {
+            blind_blocks: 0,
+            blind_a_chars: 0,
+            generous_blind_chars : 0,
+        };
+
+        let mut match_interval = Interval {
+            lower: 0,
+            upper: self.bwt().len() - 1,
+        };
+        for (match_len, &p_char) in (1..).zip(pattern.iter().rev()) {
+            if config.alphabet.is_wildcard(p_char) {
+                // the wildcard is an error against anything
+                break;
+            }
+            let less = self.less(p_char);
+            match_interval = Interval{
+                lower : less + if match_interval.lower > 0 { self.occ(match_interval.lower - 1, p_char) } else { 0 },
+                upper : less + self.occ(match_interval.upper, p_char) - 1,
+            };
+            if match_interval.lower > match_interval.upper{
+                // nothing left matching the pattern suffix
+                break;
+            }
+            if match_len < config.thresh as usize {
+                continue;
+            }
+            // Add candidates to set for matched b strings preceded by '$'
+            let a = b'$';
+            let less = self.less(a);
+            let dollar_interval = Interval {
+                lower : less + if match_interval.lower > 0 { self.occ(match_interval.lower - 1, a) } else { 0 },
+                upper : less + self.occ(match_interval.upper, a),
+            }; //final interval must have exclusive end
+            let positions = index.locate(&dollar_interval);
+            if !positions.is_empty(){
+                add_candidates_from_positions(positions, &mut candidate_set, &p_cns, &s_cns, match_len, match_len, false);
+            }
+            if match_len == patt_len && config.inclusions {
+                // Add inclusion candidates to set for everything containing the entire pattern
+                let inclusion_interval = Interval{
+                    lower : match_interval.lower,
+                    upper : match_interval.upper + 1,
+                }; // final interval must have exclusive end
+                let positions = index.locate(&inclusion_interval);
+                if !positions.is_empty(){
+                    add_candidates_from_positions(positions, &mut candidate_set, &p_cns, &s_cns, match_len, match_len, true);
+                }
+            }
+        }
+        candidate_set
+    }
+
     /*
     This conceptually corresponds to the search for one FILTER of the candidate.
     The call branches recursively as specified by the functions used for the algorithm mode.
     Various information that changes with each iteration is stored on the call stack directly.
+    "errors" counts the errors of the match (as the mode's functions do), and "cost" adds up their costs (see EditCosts),
+    which can't exceed the error limit of the longest overlap the pattern may have.
     */
+    #[allow(clippy::too_many_arguments)]
     fn recurse_candidates(&self,
                           cand_set : &mut HashSet<Candidate>,
                           p_cns : &PatternConstants,
                           s_cns : &SuffixConstants,
                           errors : i32,
+                          cost : u32,
                           p_i : i32,
                           last_operation : LastOperation,
                           a_match_len : usize,
@@ -144,8 +259,8 @@ pub trait GeneratesCandidates : FMIndexable {
             None    => p_cns.patt_blocks - s_cns.blind_blocks,
         };
         //look up how many errors are allowed from the filter module
-        let permitted_errors : i32 = min(p_cns.hard_error_cap,
-                                         p_cns.mode.filter_func(completed_blocks, p_cns.patt_blocks, s_cns.blind_blocks));
+        let permitted_errors : i32 = p_cns.mode.filter_func(completed_blocks, p_cns.patt_blocks, s_cns.blind_blocks);
+        let (substitution_cost, indel_cost) = p_cns.branch_costs();
 
         //Design decision: if the lengths of A and B differ, we are generous with the size for lookups
 
@@ -160,8 +275,8 @@ pub trait GeneratesCandidates : FMIndexable {
                 lower : less + if match_interval.lower > 0 { self.occ(match_interval.lower - 1, a) } else { 0 },
                 upper : less + self.occ(match_interval.upper, a),
             }; //final interval must have exclusive end
-            let positions = dollar_interval.occ(p_cns.sa);
-            if positions.len() > 0{
+            let positions = p_cns.index.locate(&dollar_interval);
+            if !positions.is_empty(){
                 add_candidates_from_positions(positions, cand_set, p_cns, s_cns, a_match_len, b_match_len, false);
             }
         }
@@ -175,8 +290,8 @@ pub trait GeneratesCandidates : FMIndexable {
                     lower : match_interval.lower,
                     upper : match_interval.upper + 1,
                 }; // final interval must have exclusive end
-                let positions = inclusion_interval.occ(p_cns.sa);
-                if positions.len() > 0{
+                let positions = p_cns.index.locate(&inclusion_interval);
+                if !positions.is_empty(){
                     add_candidates_from_positions(positions, cand_set, p_cns, s_cns, a_match_len, b_match_len, true);
                 }
             }
@@ -186,14 +301,18 @@ pub trait GeneratesCandidates : FMIndexable {
 
         // consider a new derived b string match, one char longer (in front) than existing match
         let p_char = *p_cns.pattern.get(p_i as usize).unwrap();
-        for &a in p_cns.config.alphabet() {
+        for &a in p_cns.config.index_symbols() {
             let less = self.less(a);
             let next_interval = Interval{
                 lower : less + if match_interval.lower > 0 { self.occ(match_interval.lower - 1, a) } else { 0 },
                 upper : less + self.occ(match_interval.upper, a) - 1,
             };
-            let recurse_errors =  if p_char == a && a != READ_ERR {errors} else {errors + 1};
-            if recurse_errors <= permitted_errors {
+            let (recurse_errors, recurse_cost) = if p_char == a && !p_cns.config.alphabet.is_wildcard(a) {
+                (errors, cost)
+            } else {
+                (errors + 1, cost + substitution_cost)
+            };
+            if recurse_errors <= permitted_errors && recurse_cost <= p_cns.cost_limit {
                 // recursively explore SUBSTITUTION cases (both hamming and levenshtein)
                 stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
                     // guaranteed to have at least 32K of stack
@@ -201,6 +320,7 @@ pub trait GeneratesCandidates : FMIndexable {
                                             p_cns,
                                             s_cns,
                                             recurse_errors,
+                                            recurse_cost,
                                             p_i-1,  //step left
                                             LastOperation::Substitution,
                                             a_match_len + 1,
@@ -209,27 +329,27 @@ pub trait GeneratesCandidates : FMIndexable {
                                             );
                 });
             }
-            if (errors < permitted_errors) && p_cns.config.edit_distance && last_operation.allows_insertion() {
-                if p_char != a{
-                    // recursively explore INSERTION cases (if levenshtein)
-                    stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
-                        // guaranteed to have at least 32K of stack
-                        self.recurse_candidates(cand_set,
-                                                p_cns,
-                                                s_cns,
-                                                errors + 1, //always induces an error
-                                                p_i,        //don't step left
-                                                LastOperation::Insertion,
-                                                a_match_len,//the pattern string doesn't grow
-                                                b_match_len + 1,
-                                                &next_interval,
-                                                );
-                    });
-                }
+            if (errors < permitted_errors) && cost + indel_cost <= p_cns.cost_limit && p_cns.config.edit_distance
+                    && last_operation.allows_insertion() && p_char != a {
+                // recursively explore INSERTION cases (if levenshtein)
+                stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
+                    // guaranteed to have at least 32K of stack
+                    self.recurse_candidates(cand_set,
+                                            p_cns,
+                                            s_cns,
+                                            errors + 1, //always induces an error
+                                            cost + indel_cost,
+                                            p_i,        //don't step left
+                                            LastOperation::Insertion,
+                                            a_match_len,//the pattern string doesn't grow
+                                            b_match_len + 1,
+                                            &next_interval,
+                                            );
+                });
             }
         }
 
-        if p_cns.config.edit_distance && errors < permitted_errors && !pattern_finished{
+        if p_cns.config.edit_distance && errors < permitted_errors && cost + indel_cost <= p_cns.cost_limit && !pattern_finished{
             // recursively explore DELETION cases (if levenshtein) and have at least 1 spare pattern char to jump over
             if last_operation.allows_deletion(){
 
@@ -241,11 +361,12 @@ pub trait GeneratesCandidates : FMIndexable {
                                             p_cns,
                                             s_cns,
                                             errors + 1,
+                                            cost + indel_cost,
                                             p_i - 1,         //one step without matching
                                             LastOperation::Deletion,
                                             a_match_len + 1,
                                             b_match_len,     //the matched string doesn't grow
-                                            &match_interval, //stays unchanged
+                                            match_interval, //stays unchanged
                                             );
                 });
             }
@@ -253,6 +374,133 @@ pub trait GeneratesCandidates : FMIndexable {
     }
 }
 
+/*
+The counterpart of GeneratesCandidates::recurse_candidates for the bidirectional index, matching the blocks
+of the filter in the order of the mode (see IsMode::block_order).
+The matched section of the pattern is left_i+1..right_i (in the frame of the text, see clarification.txt).
+Blocks matched FORWARDS grow it to the left (as recurse_candidates does) and blocks matched BACKWARDS to the right.
+"step" is the position in the order of the block being matched, and as all blocks before it are completed,
+it is the completed_blocks of the mode's functions.
+Candidates are only generated once the match reaches the right end of the filter, so that the '$' preceding it
+marks the end of an overlap. "left_operation" is the latest operation on the left end of the match, which
+restricts candidates as last_operation does in recurse_candidates.
+*/
+#[allow(clippy::too_many_arguments)]
+fn recurse_bidirectional(bi : &BidirectionalIndex,
+                         cand_set : &mut HashSet<Candidate>,
+                         p_cns : &PatternConstants,
+                         s_cns : &SuffixConstants,
+                         f_cns : &FilterConstants,
+                         errors : i32,
+                         cost : u32,
+                         left_i : i32,
+                         right_i : i32,
+                         step : usize,
+                         last_operation : LastOperation,
+                         left_operation : LastOperation,
+                         a_match_len : usize,
+                         b_match_len : usize,
+                         match_interval : &BiInterval,
+                         ){
+    if match_interval.is_empty(){
+        // no match left -> prune branch
+        return
+    }
+
+    let mut step = step;
+    let mut last_operation = last_operation;
+    while step < f_cns.directions.len() && f_cns.completes(step, left_i, right_i) {
+        step += 1;
+        if step < f_cns.directions.len() && f_cns.directions[step] != f_cns.directions[step-1] {
+            // the operations so far were at the other end of the match, and don't restrict the next one
+            last_operation = LastOperation::Substitution;
+        }
+    }
+    let completed_blocks = step as i32;
+    let (lower_bound, upper_bound) = p_cns.mode.error_bounds(f_cns.search, completed_blocks, p_cns.patt_blocks, s_cns.blind_blocks);
+    if errors < lower_bound || errors > upper_bound {
+        // left to another search
+        return
+    }
+    let permitted_errors : i32 = upper_bound;
+    let (substitution_cost, indel_cost) = p_cns.branch_costs();
+
+    let generous_overlap_len = max(a_match_len, b_match_len) + s_cns.generous_blind_chars;
+    let cand_condition_satisfied = right_i == f_cns.right_end && left_operation.allows_candidates() &&
+        p_cns.mode.candidate_condition(generous_overlap_len as i32, completed_blocks, p_cns.config.thresh, errors);
+    if cand_condition_satisfied {
+        // Add candidates to set for matched b strings preceded by '$'
+        let dollar_interval = bi.extend_left(match_interval, b'$');
+        if !dollar_interval.is_empty() {
+            let positions = p_cns.index.locate(&dollar_interval.forward());
+            add_candidates_from_positions(positions, cand_set, p_cns, s_cns, a_match_len, b_match_len, false);
+        }
+    }
+
+    let filter_finished = step == f_cns.directions.len();
+    if filter_finished {
+        // the match spans the filter, up to the left end of the pattern.
+        // Add inclusion candidates to set at this position for everything in the remaining range
+        if p_cns.config.inclusions && cand_condition_satisfied {
+            let positions = p_cns.index.locate(&match_interval.forward());
+            add_candidates_from_positions(positions, cand_set, p_cns, s_cns, a_match_len, b_match_len, true);
+        }
+        return;
+    }
+
+    let direction = f_cns.directions[step];
+    // the pattern index of the next matched character, and the match boundaries once it is matched
+    let (p_i, next_left_i, next_right_i) = match direction {
+        Direction::Forwards => (left_i, left_i - 1, right_i),
+        Direction::Backwards => (right_i, left_i, right_i + 1),
+    };
+    // operations on the left end of the match replace the left_operation
+    let at_left = |operation : LastOperation| {
+        if direction == Direction::Forwards {operation} else {left_operation}
+    };
+    let p_char = *p_cns.pattern.get(p_i as usize).unwrap();
+    for &a in p_cns.config.index_symbols() {
+        let next_interval = match direction {
+            Direction::Forwards => bi.extend_left(match_interval, a),
+            Direction::Backwards => bi.extend_right(match_interval, a),
+        };
+        let (recurse_errors, recurse_cost) = if p_char == a && !p_cns.config.alphabet.is_wildcard(a) {
+            (errors, cost)
+        } else {
+            (errors + 1, cost + substitution_cost)
+        };
+        if recurse_errors <= permitted_errors && recurse_cost <= p_cns.cost_limit {
+            // SUBSTITUTION
+            stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
+                recurse_bidirectional(bi, cand_set, p_cns, s_cns, f_cns, recurse_errors, recurse_cost,
+                                      next_left_i, next_right_i, step,
+                                      LastOperation::Substitution, at_left(LastOperation::Substitution),
+                                      a_match_len + 1, b_match_len + 1, &next_interval);
+            });
+        }
+        if errors < permitted_errors && cost + indel_cost <= p_cns.cost_limit && p_cns.config.edit_distance
+                && last_operation.allows_insertion() && p_char != a {
+            // INSERTION. the pattern doesn't step
+            stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
+                recurse_bidirectional(bi, cand_set, p_cns, s_cns, f_cns, errors + 1, cost + indel_cost,
+                                      left_i, right_i, step,
+                                      LastOperation::Insertion, at_left(LastOperation::Insertion),
+                                      a_match_len, b_match_len + 1, &next_interval);
+            });
+        }
+    }
+    if errors < permitted_errors && cost + indel_cost <= p_cns.cost_limit && p_cns.config.edit_distance
+            && last_operation.allows_deletion() {
+        // DELETION. the matched string doesn't grow
+        stacker::maybe_grow(32 * 1024, 1024 * 1024, || {
+            recurse_bidirectional(bi, cand_set, p_cns, s_cns, f_cns, errors + 1, cost + indel_cost,
+                                  next_left_i, next_right_i, step,
+                                  LastOperation::Deletion, at_left(LastOperation::Deletion),
+                                  a_match_len + 1, b_match_len, match_interval);
+        });
+    }
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum LastOperation{
     Initial,
@@ -312,7 +560,8 @@ fn add_candidates_from_positions(positions : Vec<usize>,
         if p_cns.config.reversals && !inclusion{
             //don't need this candidate. A complementary candidate (that verifies to same solution)
             //will be found by a partner task for which id_a < id_b
-            if p_cns.id_a > id_b {continue;}
+            //(only if b gets a task at all, see PatternConstants)
+            if p_cns.id_a > id_b && p_cns.has_task(id_b) {continue;}
         }
         let a_len = p_cns.pattern.len();
         let b_len = p_cns.maps.get_length(id_b);
@@ -321,7 +570,7 @@ fn add_candidates_from_positions(positions : Vec<usize>,
             a_len == a_match_len && b_len == b_match_len && a_len == b_len {
             //perfect complete match. A very niche case where inclusions will be found twice
             //discards one of them
-            if p_cns.id_a > id_b {
+            if p_cns.id_a > id_b && p_cns.has_task(id_b) {
                 continue;
             }
         }
@@ -350,10 +599,12 @@ fn add_candidates_from_positions(positions : Vec<usize>,
         } else {
             // b_overlap_len is unknown, but it has upper and lower bounds as determined by the
             // length of b, the error rate etc.
+            // each symbol of difference is an indel, so there can't be more of them than fit into the cost limit either
+            let max_indels = (p_cns.cost_limit / p_cns.config.costs.cheapest_indel()) as usize;
             (
-                max((a2 as f32 * (1.0-p_cns.config.err_rate)).ceil() as usize,
+                max(max((a2 as f32 * (1.0-p_cns.config.err_rate)).ceil() as usize, a2.saturating_sub(max_indels)),
                     b_match_len),
-                min((a2 as f32 / (1.0-p_cns.config.err_rate)).floor() as usize,
+                min(min((a2 as f32 / (1.0-p_cns.config.err_rate)).floor() as usize, a2 + max_indels),
                     b_len),
             )
         };
@@ -361,9 +612,8 @@ fn add_candidates_from_positions(positions : Vec<usize>,
 
         // for edit distance, numerous instantiations of b2 possible
         for b2 in possible_b2s{
-            if max(a2, b2) < p_cns.config.thresh as usize{
-                //TODO thresh should maybe be usize?
-                //not over threshhold
+            if !p_cns.config.overlap_lengths_allowed(a2, b2){
+                //not over threshhold (or the lengths are too different)
                 continue;
             }
             let b3 = b_len as i32 - b1 - (b2 as i32);
@@ -372,7 +622,7 @@ fn add_candidates_from_positions(positions : Vec<usize>,
                 continue;
             }
             let c = Candidate {
-                id_b: id_b,
+                id_b,
                 overlap_a: a2,
                 overlap_b: b2,
                 overhang_left_a: a1 - b1,
@@ -398,12 +648,71 @@ pub struct PatternConstants<'a>{
     config : &'a Config,
     maps : &'a Maps,
     block_id_lookup : &'a Vec<i32>,
-    sa : &'a RawSuffixArray,
+    index : &'a Index,
     pattern: &'a [u8],
     id_a : usize,
-    hard_error_cap : i32,
+    cost_limit : u32, // the error limit (K) of the longest overlap the pattern may have
     patt_blocks : i32,
     mode : &'a Mode,
+    // whether the reference strings (see Maps::query_ids) get tasks of their own too (see Overlapper::for_each and solve_update)
+    references_searched : bool,
+}
+
+/*
+the order of the blocks of one search of one filter (see IsMode::block_order), for recurse_bidirectional.
+*/
+pub struct FilterConstants{
+    search : i32, // see IsMode::searches
+    directions : Vec<Direction>,
+    bounds : Vec<(i32, i32)>, // the leftmost and rightmost pattern index of each block, in the order
+    right_end : i32, // one past the rightmost pattern index of the filter
+}
+
+impl FilterConstants {
+    fn new(mode : &Mode, search : i32, block_lengths : &[i32], patt_len : usize, blind_blocks : i32) -> Self {
+        let patt_blocks = block_lengths.len() as i32;
+        // block 0 is at the right end of the pattern
+        let mut block_bounds : Vec<(i32, i32)> = Vec::with_capacity(block_lengths.len());
+        let mut rightmost = patt_len as i32 - 1;
+        for &len in block_lengths.iter() {
+            block_bounds.push((rightmost - len + 1, rightmost));
+            rightmost -= len;
+        }
+        let order = mode.block_order(search, patt_blocks, blind_blocks);
+        FilterConstants {
+            search,
+            directions : block_directions(&order, patt_blocks, blind_blocks),
+            bounds : order.iter().map(|&block| block_bounds[block as usize]).collect(),
+            right_end : block_bounds[blind_blocks as usize].1 + 1,
+        }
+    }
+
+    // whether the block of the given step is matched, when the match is left_i+1..right_i
+    #[inline]
+    fn completes(&self, step : usize, left_i : i32, right_i : i32) -> bool {
+        match self.directions[step] {
+            Direction::Forwards => left_i < self.bounds[step].0,
+            Direction::Backwards => right_i > self.bounds[step].1,
+        }
+    }
+}
+
+impl<'a> PatternConstants<'a> {
+    /*
+    what a substitution and an indel add to the cost of a match.
+    An indel is an insertion or a deletion depending on which of the pattern and the B string is output first
+    (see EditCosts::swapped), which isn't known before the candidate is found, so it costs the cheaper of the two.
+    */
+    #[inline]
+    fn branch_costs(&self) -> (u32, u32) {
+        (self.config.costs.substitution, self.config.costs.cheapest_indel())
+    }
+
+    // whether the string is searched for too, so that candidates can be left to the task for the smaller id
+    #[inline]
+    fn has_task(&self, id : usize) -> bool {
+        self.references_searched || self.maps.is_query(id)
+    }
 }
 
 
@@ -1,74 +1,192 @@
-use num_cpus;
-use crate::structs::run_config::Config;
-use crate::modes::{IsMode, Mode};
-use crate::modes;
+use rust_overlaps::structs::run_config::{Config, Normalisation, OutputFormat, Alphabet, EditCosts};
+use rust_overlaps::modes::{IsMode, Mode};
+use rust_overlaps::modes;
+use rust_overlaps::Error;
 use std::cmp::{min, max};
-use clap::clap_app;
+use std::str::FromStr;
+use clap::{clap_app, ArgMatches};
 /*
-Using Clap, builds a config struct that contains all the user's input
+Using Clap, builds a config struct that contains all the user's input.
+Without a mode (-m=auto), it is selected once the input is indexed (see modes::auto)
+Values that can't be interpreted, and options that don't go together, are Parameter errors.
 */
-pub fn parse_run_args() -> (Box<dyn IsMode>, Config) {
+pub fn parse_run_args() -> Result<(Option<Box<dyn IsMode>>, Config), Error> {
     let matches = clap_app!(ASPOPsolver =>
         (version: "1.0")
         (author: "Christopher Esterhuyse <christopher.esterhuyse@gmail.com>")
         (about: "Finds approximate suffix prefix overlaps from a given fasta file")
 
-        (@arg IN_PATH: +required +takes_value "Path to the input fasta file")
+        (@arg IN_PATH: +required +takes_value "Path to the input fasta or fastq file, optionally gzip, bgzip or zstd compressed. Use '-' to read from stdin")
         (@arg OUT_PATH: +required +takes_value "Path of desired output file")
         (@arg ERR_RATE: +required +takes_value "The max rate of errors in an overlap")
         (@arg THRESH: +required +takes_value "Shortest allowed length of an overlap")
 
         (@arg worker_threads: -w --worker_threads +takes_value "Number of worker threads used. Defaults to number of logical cpu cores")
-        (@arg mode: -m --mode +takes_value "Uses the filtering scheme mode given options {valimaki, kucherov}. Modes can also be supplied string arguments i.e. 'kucherov_2'. (Default : kucherov_2")
+        (@arg min_quality: -q --min_quality +takes_value "FASTQ input only. Symbols with a phred quality below this value are replaced with the wildcard (N), counting as errors")
+        (@arg normalise: --normalise +takes_value "How to treat symbols outside the alphabet in the input. options {strict, upper, mask}. 'upper' uppercases soft-masked symbols, 'mask' also replaces IUPAC ambiguity codes with N. (Default : upper)")
+        (@arg substitution_cost: --substitution_cost +takes_value "How many errors a substitution counts as. (Default : 1)")
+        (@arg insertion_cost: --insertion_cost +takes_value "Edit distance only. How many errors an insertion (a symbol of B missing from A) counts as. (Default : 1)")
+        (@arg deletion_cost: --deletion_cost +takes_value "Edit distance only. How many errors a deletion (a symbol of A missing from B) counts as. (Default : 1)")
+        (@arg max_errors: --max_errors +takes_value "Most errors (K) an overlap may have, however long it is. Overlaps are limited by both this and ERR_RATE")
+        (@arg max_length_ratio: --max_length_ratio +takes_value "Longest allowed ratio of the overlap lengths of an overlap, the longer of OLA and OLB divided by the shorter. Only matters for edit distance")
+        (@arg alphabet: --alphabet +takes_value "The alphabet of the input strings. options {dna, rna, protein} or the symbols themselves, optionally followed by ':' and the complement of each (ie: 'ACGT:TGCA'). Reversals need complements. (Default : dna)")
+        (@arg reference: --reference +takes_value "Path to a second input file, the reference set. Only it is indexed, and only overlaps between strings of IN_PATH (the queries) and the reference set are found")
+        (@arg update: --update +takes_value "Path to the TSV output of a previous run. The strings of IN_PATH are new, and the previous strings are given with --load_index (or --reference). Only overlaps involving new strings are found and merged into the previous output")
+        (@arg save_index: --save_index +takes_value "Saves the index (and the prepared input) to this path, to be reused with --load_index")
+        (@arg load_index: --load_index +takes_value "Loads a previously saved index instead of reading and indexing IN_PATH (or the reference set). Must have been saved with the same -r and -n flags")
+        (@arg sa_sampling: --sa_sampling +takes_value "Keeps only every k-th suffix array entry, the rest are recovered by walking the index. Saves memory at the cost of speed. (Default : 1, ie: the full suffix array)")
+        (@arg occ_sampling: --occ_sampling +takes_value "Keeps only every k-th entry of the index's Occ table. Saves memory at the cost of speed. (Default : 3)")
+        (@arg sort_buffer: --sort_buffer +takes_value "Sorts the output holding at most this many solutions in memory, merging sorted runs from temporary files. Has no effect with -g")
+        (@arg temp_dir: --temp_dir +takes_value "Directory for the temporary files of --sort_buffer. Defaults to the system's temporary directory")
+        (@arg best_overlaps: --best_overlaps +takes_value "Keeps only this many of the best overlaps (longest, then fewest errors) at each end of each string")
+        (@arg transitive_fuzz: --transitive_fuzz +takes_value "How many symbols the lengths of overlaps may be off for --transitive_reduction. (Default : 10)")
+        (@arg unitigs: --unitigs +takes_value "Assembles the overlaps into unitigs, written to <PREFIX>.fasta along with their graph in <PREFIX>.gfa")
+        (@arg mode: -m --mode +takes_value "Uses the filtering scheme mode given options {valimaki, kucherov}. Modes can also be supplied string arguments i.e. 'kucherov_2'. 'scheme_<PATH>' loads a search scheme from a file. 'auto' times the modes on a sample of the input and uses the fastest. (Default : kucherov_2")
 
+        (@arg output_format: --output_format +takes_value "Format of the output file. options {tsv, paf, gfa1, gfa2} ('gfa' is short for gfa1). (Default : tsv)")
         (@arg format_line: -f --format_line "The first line of the output file will contain a TSV header line.")
+        (@arg gfa_sequences: --gfa_sequences "GFA output only. The S lines contain the input strings instead of '*'")
+        (@arg cigar: -c --cigar "Computes the alignment of each overlap and outputs it as a CIGAR string")
         (@arg reversals: -r --reversals "Enables reversals of input strings")
+        (@arg symmetric_thresh: --symmetric_thresh "Both overlap lengths (OLA and OLB) must reach THRESH, instead of only the longer one")
         (@arg inclusions: -i --inclusions "Enables finding of inclusion overlaps (one string within another)")
         (@arg edit_distance: -e --edit_distance "Uses Levenshtein / edit distance instead of Hamming distance")
         (@arg verbose: -v --verbose +multiple "Prints completed steps of the run process")
         (@arg greedy_output: -g --greedy_output "Threads print solutions to output greedily instead of storing them. Limited duplication may arise")
         (@arg print: -p --print "For each solution printed to file, also prints a rough visualization to stdout (mostly for debugging purposes)")
-        (@arg no_n: -n --no_n "Omits N symbol from alphabet saving time. Will remove N symbols from input file (with a warning)")
+        (@arg no_n: -n --no_n "Omits the wildcard symbol (N, or X for proteins) from the alphabet saving time. Will remove wildcard symbols from input file (with a warning)")
+        (@arg remove_contained: --remove_contained "Removes strings that are included in another string, along with all their overlaps")
+        (@arg transitive_reduction: --transitive_reduction "Removes overlaps that are implied by two other overlaps")
         (@arg track_progress: -t --track_progress "Prints progress bar for completed tasks and ETA to stdout")
     ).get_matches();
 
-    let worker_threads = match matches.value_of("worker_threads") {
-        Some(s) => s.parse().unwrap(),
-        None => max(1, num_cpus::get()-1),
+    let worker_threads = parse_optional(&matches, "worker_threads")?.unwrap_or(max(1, num_cpus::get()-1));
+    let mode : Option<Mode> = match matches.value_of("mode") {
+        Some("auto") => None,
+        Some(s) => Some(modes::get_mode(s)?),
+        _ => Some(modes::default_mode()),
     };
-    let mode : Mode = match matches.value_of("mode") {
-        Some(s) => modes::get_mode(s),
-        _ => modes::default_mode(),
+
+    let normalisation = match matches.value_of("normalise") {
+        Some("strict") => Normalisation::Strict,
+        Some("upper") | None => Normalisation::Upper,
+        Some("mask") => Normalisation::Mask,
+        Some(s) => return Err(Error::Parameter(format!("No normalisation policy with the name '{}' found!", s))),
+    };
+
+    let alphabet = match matches.value_of("alphabet") {
+        Some(s) => Alphabet::parse(s).map_err(|e| Error::Parameter(format!("Invalid alphabet '{}': {}", s, e)))?,
+        None => Alphabet::dna(),
+    };
+
+    let output_format = match matches.value_of("output_format") {
+        Some("tsv") | None => OutputFormat::Tsv,
+        Some("gfa") | Some("gfa1") => OutputFormat::Gfa1,
+        Some("gfa2") => OutputFormat::Gfa2,
+        Some("paf") => OutputFormat::Paf,
+        Some(s) => return Err(Error::Parameter(format!("No output format with the name '{}' found!", s))),
+    };
+
+    let costs = EditCosts{
+        substitution :  parse_optional(&matches, "substitution_cost")?.unwrap_or(1),
+        insertion :     parse_optional(&matches, "insertion_cost")?.unwrap_or(1),
+        deletion :      parse_optional(&matches, "deletion_cost")?.unwrap_or(1),
     };
 
     let config = Config{
         //required
         input  :            matches.value_of("IN_PATH").unwrap().to_owned(),
         output :            matches.value_of("OUT_PATH").unwrap().to_owned(),
-        err_rate :          matches.value_of("ERR_RATE").unwrap().parse().unwrap(),
-        thresh :            matches.value_of("THRESH").unwrap().parse().unwrap(),
+        err_rate :          parse_required(&matches, "ERR_RATE")?,
+        thresh :            parse_required(&matches, "THRESH")?,
 
         //options
-        worker_threads :    worker_threads,
+        worker_threads,
+        costs,
+        max_errors :        parse_optional(&matches, "max_errors")?,
+        max_length_ratio :  parse_optional(&matches, "max_length_ratio")?,
+        min_quality :       parse_optional(&matches, "min_quality")?,
+        normalisation,
+        alphabet,
+        output_format,
+        reference :         matches.value_of("reference").map(|s| s.to_owned()),
+        update :            matches.value_of("update").map(|s| s.to_owned()),
+        save_index :        matches.value_of("save_index").map(|s| s.to_owned()),
+        load_index :        matches.value_of("load_index").map(|s| s.to_owned()),
+        sort_buffer :       parse_optional(&matches, "sort_buffer")?,
+        temp_dir :          matches.value_of("temp_dir").map(|s| s.to_owned()),
+        unitigs :           matches.value_of("unitigs").map(|s| s.to_owned()),
+        best_overlaps :     parse_optional(&matches, "best_overlaps")?,
+        transitive_fuzz :   parse_optional(&matches, "transitive_fuzz")?.unwrap_or(10),
+        sa_sampling :       parse_optional(&matches, "sa_sampling")?.unwrap_or(1),
+        occ_sampling :      parse_optional(&matches, "occ_sampling")?.unwrap_or(3),
         verbosity:          min(matches.occurrences_of("verbose") as u8, 2),
 
         //opt-in
-        reversals :         if matches.occurrences_of("reversals")        >= 1 {true} else {false},
-        inclusions :        if matches.occurrences_of("inclusions")       >= 1 {true} else {false},
-        edit_distance :     if matches.occurrences_of("edit_distance")    >= 1 {true} else {false},
-        greedy_output:      if matches.occurrences_of("greedy_output")    >= 1 {true} else {false},
-        print:              if matches.occurrences_of("print")            >= 1 {true} else {false},
-        track_progress:     if matches.occurrences_of("track_progress")   >= 1 {true} else {false},
-        format_line:        if matches.occurrences_of("format_line")      >= 1 {true} else {false},
+        reversals :         matches.occurrences_of("reversals")        >= 1,
+        symmetric_thresh :  matches.occurrences_of("symmetric_thresh") >= 1,
+        inclusions :        matches.occurrences_of("inclusions")       >= 1,
+        edit_distance :     matches.occurrences_of("edit_distance")    >= 1,
+        greedy_output:      matches.occurrences_of("greedy_output")    >= 1,
+        print:              matches.occurrences_of("print")            >= 1,
+        track_progress:     matches.occurrences_of("track_progress")   >= 1,
+        format_line:        matches.occurrences_of("format_line")      >= 1,
+        gfa_sequences:      matches.occurrences_of("gfa_sequences")    >= 1,
+        cigar:              matches.occurrences_of("cigar")            >= 1,
+        remove_contained:   matches.occurrences_of("remove_contained") >= 1,
+        transitive_reduction: matches.occurrences_of("transitive_reduction") >= 1,
 
         //opt-out
-        n_alphabet :        if matches.occurrences_of("no_n")             == 0 {true} else {false},
+        n_alphabet :        matches.occurrences_of("no_n")             == 0,
     };
 
-    assert!(config.thresh > 0, "ERROR! Threshold value must be strictly larger than 0.");
-    assert!(config.err_rate >= 0.0 && config.err_rate < 1.0, "ERROR! Error rate limit must be non-negative and smaller than 1. 0 <= e < 1.");
+    check(config.thresh > 0, "Threshold value must be strictly larger than 0.")?;
+    check(config.err_rate >= 0.0 && config.err_rate < 1.0, "Error rate limit must be non-negative and smaller than 1. 0 <= e < 1.")?;
+    check(config.costs.substitution > 0 && config.costs.insertion > 0 && config.costs.deletion > 0,
+            "The cost of each kind of error must be strictly larger than 0.")?;
+    check(config.max_length_ratio.is_none_or(|r| r >= 1.0), "The overlap length ratio can't be smaller than 1.")?;
+    if let Some(ref mode) = mode {
+        modes::check_mode(mode.as_ref(), &config)?;
+    }
+    check(config.min_quality.is_none() || (config.n_alphabet && config.alphabet.wildcard.is_some()),
+            "Quality masking replaces symbols with the wildcard. Can't be combined with --no_n or an alphabet without one.")?;
+    check(!config.reversals || config.alphabet.has_complements(),
+            &format!("The {} alphabet has no complements. Can't be combined with -r.", config.alphabet.name))?;
+    check(!config.needs_all_solutions() || (!config.greedy_output && config.sort_buffer.is_none()),
+            "Filtering solutions and unitigs need all of them in memory. Can't be combined with -g or --sort_buffer.")?;
+    if config.unitigs.is_some() && !config.inclusions {
+        println!("    WARNING\n\tUnitigs are assembled without knowing which strings are contained in others.\n\t\
+        Run with flag -i to find inclusions and leave contained strings out.");
+    }
+    if let Some(ref previous) = config.update {
+        check(config.load_index.is_some() || config.reference.is_some(),
+                "The previous strings must be given with --load_index or --reference to --update.")?;
+        check(config.output_format == OutputFormat::Tsv && !config.greedy_output && config.sort_buffer.is_none(),
+                "Updating merges sorted TSV output. Can't be combined with other output formats, -g or --sort_buffer.")?;
+        check(!config.needs_all_solutions(),
+                "Filtering solutions and unitigs need all of them. Can't be combined with --update.")?;
+        check(previous != &config.output, "The updated output can't overwrite the previous output.")?;
+    }
+    check(config.sort_buffer != Some(0), "The sort buffer must hold at least one solution.")?;
+    check(config.sa_sampling > 0 && config.occ_sampling > 0, "Sampling rates must be strictly larger than 0.")?;
     if !config.reversals{
         println!("WARNING! Reversals are NOT enabled by default. Run with -r flag to enable reversals.");
     }
-    (mode, config)
+    Ok((mode, config))
+}
+
+fn check(condition : bool, msg : &str) -> Result<(), Error> {
+    if condition {Ok(())} else {Err(Error::Parameter(msg.to_owned()))}
+}
+
+fn parse_value<T : FromStr>(name : &str, value : &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::Parameter(format!("Couldn't interpret the value '{}' of {}.", value, name)))
+}
+
+fn parse_required<T : FromStr>(matches : &ArgMatches, name : &str) -> Result<T, Error> {
+    parse_value(name, matches.value_of(name).unwrap())
+}
+
+fn parse_optional<T : FromStr>(matches : &ArgMatches, name : &str) -> Result<Option<T>, Error> {
+    matches.value_of(name).map(|s| parse_value(name, s)).transpose()
 }
@@ -0,0 +1,219 @@
+use crate::structs::solutions::Solution;
+use crate::structs::run_config::{Config, Maps};
+use crate::filter::{self, Edge, dovetail_edges, read_of};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::{Write, BufWriter};
+
+/*
+A minimal layout stage, turning the overlaps into unitigs: the sequences spelled by the
+paths through the string graph that don't branch.
+
+The string graph is bidirected. Each input string is a pair of vertices, one for either orientation
+(see filter::dovetail_edges), and every overlap of two string ends is an edge and its reverse complement.
+Contained strings are left out, as are transitive edges. Between two vertices only the edge
+with the longest overlap (smallest offset) is kept.
+*/
+pub struct StringGraph {
+    out_edges : Vec<Vec<Edge>>, //for each vertex
+    contained : HashSet<usize>, //reads
+}
+
+/*
+a path through the string graph. offsets[i] is the offset of the edge from vertices[i] to vertices[i+1],
+so a unitig's sequence is the first offsets[i] symbols of each vertex followed by the entire last vertex.
+*/
+#[derive(Debug, PartialEq)]
+pub struct Unitig {
+    pub vertices : Vec<usize>,
+    pub offsets : Vec<i32>,
+}
+
+#[inline]
+fn flip(v : usize) -> usize {
+    v ^ 1
+}
+
+impl StringGraph {
+    pub fn build(solutions : &[Solution], maps : &Maps, config : &Config) -> StringGraph {
+        let num_reads = if config.reversals {maps.num_ids() / 2} else {maps.num_ids()};
+        let contained = filter::contained_reads(solutions, config);
+        let dovetails : Vec<Solution> = solutions.iter()
+            .filter(|s| !contained.contains(&read_of(s.id_a, config)) && !contained.contains(&read_of(s.id_b, config)))
+            .cloned()
+            .collect();
+        let reduced = filter::transitive_reduction(dovetails, config);
+
+        let mut out_edges : Vec<Vec<Edge>> = vec![Vec::new(); 2 * num_reads];
+        for s in reduced.iter() {
+            if let Some((forwards, backwards)) = dovetail_edges(s, config) {
+                for &edge in [forwards, backwards].iter() {
+                    let edges = &mut out_edges[edge.from];
+                    match edges.iter().position(|e| e.to == edge.to) {
+                        Some(i) => if edge.offset < edges[i].offset {edges[i] = edge;},
+                        None => edges.push(edge),
+                    }
+                }
+            }
+        }
+        if config.verbosity >= 2 {
+            println!("OK built string graph with {} edges.", out_edges.iter().map(|e| e.len()).sum::<usize>() / 2);
+        }
+        StringGraph {
+            out_edges,
+            contained,
+        }
+    }
+
+    pub fn num_reads(&self) -> usize {
+        self.out_edges.len() / 2
+    }
+
+    pub(crate) fn out_edges(&self, v : usize) -> &[Edge] {
+        &self.out_edges[v]
+    }
+
+    // the edge leaving v, if it is the only one leaving v and the only one entering the next vertex
+    fn unique_successor(&self, v : usize) -> Option<Edge> {
+        match self.out_edges[v].as_slice() {
+            [edge] if self.out_edges[flip(edge.to)].len() == 1 => Some(*edge),
+            _ => None,
+        }
+    }
+
+    // as unique_successor, in the other direction (the complement of the edge leaving the flipped vertex)
+    fn unique_predecessor(&self, v : usize) -> Option<usize> {
+        self.unique_successor(flip(v)).map(|edge| flip(edge.to))
+    }
+
+    /*
+    every string that isn't contained is in exactly one unitig (in one of its orientations).
+    From each string not yet in a unitig, walk back to the start of its non-branching path and
+    then forward to its end. Cycles are broken where the walk would visit a string a second time.
+    */
+    pub fn unitigs(&self) -> Vec<Unitig> {
+        let mut used = vec![false; self.num_reads()];
+        let mut unitigs = Vec::new();
+        for read in 0..self.num_reads() {
+            if used[read] || self.contained.contains(&read) {
+                continue;
+            }
+            let mut start = 2 * read;
+            let mut seen : HashSet<usize> = HashSet::new();
+            seen.insert(read);
+            while let Some(p) = self.unique_predecessor(start) {
+                if !seen.insert(p / 2) {break;}
+                start = p;
+            }
+
+            let mut unitig = Unitig{vertices : vec![start], offsets : vec![]};
+            seen.clear();
+            seen.insert(start / 2);
+            let mut v = start;
+            while let Some(edge) = self.unique_successor(v) {
+                if !seen.insert(edge.to / 2) {break;}
+                unitig.vertices.push(edge.to);
+                unitig.offsets.push(edge.offset);
+                v = edge.to;
+            }
+            for &v in unitig.vertices.iter() {
+                used[v / 2] = true;
+            }
+            unitigs.push(unitig);
+        }
+        unitigs
+    }
+}
+
+// the id of the string of a vertex. with reversals the reversed strings have their own (odd) ids
+#[inline]
+fn vertex_id(v : usize, config : &Config) -> usize {
+    if config.reversals {v} else {v / 2}
+}
+
+// the string of a vertex as it is oriented (see Unitig)
+pub fn vertex_sequence(v : usize, maps : &Maps, config : &Config) -> Vec<u8> {
+    let mut sequence = maps.get_string(vertex_id(v, config)).to_vec();
+    sequence.reverse(); // the text is backwards
+    if !config.reversals && v % 2 == 1 {
+        sequence = config.alphabet.reverse_complement(&sequence);
+    }
+    sequence
+}
+
+pub fn unitig_sequence(unitig : &Unitig, maps : &Maps, config : &Config) -> Vec<u8> {
+    let mut sequence = Vec::new();
+    for (i, &v) in unitig.vertices.iter().enumerate() {
+        let vertex_sequence = vertex_sequence(v, maps, config);
+        match unitig.offsets.get(i) {
+            Some(&offset) => sequence.extend_from_slice(&vertex_sequence[..offset as usize]),
+            None => sequence.extend_from_slice(&vertex_sequence),
+        }
+    }
+    sequence
+}
+
+fn vertex_name(v : usize, maps : &Maps, config : &Config) -> String {
+    format!("{}{}", maps.get_name_for(vertex_id(v, config)), if v & 1 == 0 {'+'} else {'-'})
+}
+
+/*
+builds the string graph from the solutions and writes its unitigs to <prefix>.fasta,
+and the graph of unitigs to <prefix>.gfa (GFA1, with an L line wherever unitigs join).
+returns the number of unitigs.
+*/
+pub fn write_unitigs(prefix : &str, solutions : &[Solution], maps : &Maps, config : &Config) -> Result<usize, io::Error> {
+    let graph = StringGraph::build(solutions, maps, config);
+    let unitigs = graph.unitigs();
+    let names : Vec<String> = (0..unitigs.len()).map(|i| format!("utg{}", i + 1)).collect();
+
+    let mut fasta = BufWriter::new(File::create(format!("{}.fasta", prefix))?);
+    let mut gfa = BufWriter::new(File::create(format!("{}.gfa", prefix))?);
+    gfa.write_all(b"H\tVN:Z:1.0\n")?;
+    for (unitig, name) in unitigs.iter().zip(names.iter()) {
+        // the symbols of the input, written as they are
+        let sequence = unitig_sequence(unitig, maps, config);
+        let reads : Vec<String> = unitig.vertices.iter().map(|&v| vertex_name(v, maps, config)).collect();
+        writeln!(fasta, ">{} LN:i:{} reads={}", name, sequence.len(), reads.join(","))?;
+        fasta.write_all(&sequence)?;
+        fasta.write_all(b"\n")?;
+        write!(gfa, "S\t{}\t", name)?;
+        gfa.write_all(&sequence)?;
+        writeln!(gfa, "\tLN:i:{}", sequence.len())?;
+    }
+
+    // the oriented unitig that starts (or ends) with a vertex
+    let mut starting : HashMap<usize, (usize, char)> = HashMap::new();
+    let mut ending : HashMap<usize, (usize, char)> = HashMap::new();
+    let mut internal : HashSet<(usize, usize)> = HashSet::new();
+    for (i, unitig) in unitigs.iter().enumerate() {
+        let first = unitig.vertices[0];
+        let last = *unitig.vertices.last().unwrap();
+        starting.insert(first, (i, '+'));
+        starting.insert(flip(last), (i, '-'));
+        ending.insert(last, (i, '+'));
+        ending.insert(flip(first), (i, '-'));
+        for pair in unitig.vertices.windows(2) {
+            internal.insert((pair[0], pair[1]));
+            internal.insert((flip(pair[1]), flip(pair[0])));
+        }
+    }
+    let mut written : HashSet<(usize, usize)> = HashSet::new();
+    for from in 0..graph.out_edges.len() {
+        for edge in graph.out_edges(from).iter() {
+            if internal.contains(&(edge.from, edge.to)) || written.contains(&(flip(edge.to), flip(edge.from))) {
+                continue;
+            }
+            if let (Some(&(u, u_or)), Some(&(w, w_or))) = (ending.get(&edge.from), starting.get(&edge.to)) {
+                let overlap = maps.get_length(vertex_id(edge.from, config)) as i32 - edge.offset;
+                writeln!(gfa, "L\t{}\t{}\t{}\t{}\t{}M", names[u], u_or, names[w], w_or, overlap)?;
+                written.insert((edge.from, edge.to));
+            }
+        }
+    }
+    fasta.flush()?;
+    gfa.flush()?;
+    Ok(unitigs.len())
+}
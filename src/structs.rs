@@ -58,6 +58,13 @@ pub mod solutions{
         }
     }
 
+    /*
+    alignment of the overlapping sections of A (the reference) and B as (length, operation) pairs, left to right.
+    b'M' aligns a symbol of each string (match or substitution),
+    b'I' is a symbol only in B and b'D' a symbol only in A.
+    */
+    pub type Cigar = Vec<(usize, u8)>;
+
     //oriented
     #[derive(Debug,Clone)]
     pub struct Solution{
@@ -69,6 +76,7 @@ pub mod solutions{
         pub overlap_a : usize,
         pub overlap_b : usize,
         pub errors : u32,
+        pub cigar : Option<Cigar>, // only if asked for in the config
     }
 
     impl Solution{
@@ -77,6 +85,15 @@ pub mod solutions{
             self.overhang_right_b *= -1;
             swap(&mut self.id_a, &mut self.id_b);
             swap(&mut self.overlap_a, &mut self.overlap_b);
+            if let Some(ref mut cigar) = self.cigar {
+                for op in cigar.iter_mut() {
+                    op.1 = match op.1 {
+                        b'I' => b'D',
+                        b'D' => b'I',
+                        x => x,
+                    };
+                }
+            }
         }
 
         pub fn h_flip(&mut self, reversals : bool){
@@ -90,6 +107,9 @@ pub mod solutions{
             swap(&mut self.overhang_left_a, &mut self.overhang_right_b);
             self.overhang_left_a *= -1;
             self.overhang_right_b *= -1;
+            if let Some(ref mut cigar) = self.cigar {
+                cigar.reverse();
+            }
         }
     }
 
@@ -138,6 +158,9 @@ representations and is queried throughout the run, also never changing after bei
 pub mod run_config{
     extern crate bidir_map;
     use bidir_map::BidirMap;
+    use std::cmp::{min, max};
+    use std::borrow::Cow;
+    use std::ops::Range;
 
     #[derive(Debug)]
     pub struct Maps{
@@ -145,6 +168,7 @@ pub mod run_config{
         pub id2name_vec : Vec<String>,
         pub id2index_bdmap : BidirMap<usize, usize>,
         pub indexes : Vec<usize>,
+        pub first_query_id : usize, // ids below are the reference set. 0 unless queries were appended
     }
 
     impl Maps{
@@ -153,6 +177,39 @@ pub mod run_config{
             self.id2index_bdmap.len()
         }
 
+        // the strings to search for. Without a reference set, that is all of them
+        pub fn query_ids(&self) -> Range<usize> {
+            self.first_query_id..self.num_ids()
+        }
+
+        #[inline]
+        pub fn is_query(&self, id : usize) -> bool {
+            id >= self.first_query_id
+        }
+
+        // the strings indexed by default: the reference set, or all of them if there are no queries
+        pub fn reference_ids(&self) -> Range<usize> {
+            0..if self.first_query_id == 0 {self.num_ids()} else {self.first_query_id}
+        }
+
+        /*
+        the part of the text with the strings of the given ids, to be indexed on its own, and its offset in the text.
+        It begins with the '$' of the first string and is terminated by a '#' (put in place in a copy
+        unless the part runs to the end of the text).
+        */
+        pub fn text_of(&self, ids : &Range<usize>) -> (usize, Cow<'_, [u8]>) {
+            assert!(ids.start < ids.end && ids.end <= self.num_ids());
+            let start = self.index_for(ids.start) - 1;
+            if ids.end == self.num_ids() {
+                (start, Cow::Borrowed(&self.text[start..]))
+            } else {
+                let end = self.index_for(ids.end) - 1;
+                let mut text = self.text[start..end].to_vec();
+                text.push(b'#');
+                (start, Cow::Owned(text))
+            }
+        }
+
         pub fn get_string(&self, id : usize) -> &[u8]{
             assert!(id < self.num_ids());
             &self.text[*self.id2index_bdmap.get_by_first(&id).unwrap()..self.get_end_index(id)]
@@ -188,19 +245,202 @@ pub mod run_config{
         #[inline]
         pub fn id_for(&self, id : usize) -> usize{
             *(self.id2index_bdmap.get_by_second(&id)
-                .expect(&format!("no index for ID {}. input has IDs from 0 --> {}",
+                .unwrap_or_else(|| panic!("no index for ID {}. input has IDs from 0 --> {}",
                                 id, self.num_ids())))
         }
 
         #[inline]
         pub fn index_for(&self, index : usize) -> usize{
             *(self.id2index_bdmap.get_by_first(&index)
-                .expect(&format!("no id at index {}", index)))
+                .unwrap_or_else(|| panic!("no id at index {}", index)))
+        }
+    }
+
+    /*
+    how input symbols outside of the configured alphabet (see Alphabet) are treated when preparing the text
+    Strict : anything not in the alphabet is rejected
+    Upper : lowercase (soft-masked) symbols are uppercased, the alphabet's ambiguity codes are rejected
+    Mask : as Upper, but the alphabet's ambiguity codes are replaced with its wildcard (rejected if it has none)
+    */
+    #[derive(Debug, PartialEq, Copy, Clone)]
+    pub enum Normalisation{
+        Strict,
+        Upper,
+        Mask,
+    }
+
+    /*
+    what each kind of error adds to the error count K of a solution.
+    Insertions are symbols of B missing from A, and deletions symbols of A missing from B,
+    for A and B as they are output. With Hamming distance there are only substitutions.
+    Costs are at least 1, so a solution has no more than K / cheapest() errors of any kind.
+    */
+    #[derive(Debug, PartialEq, Copy, Clone)]
+    pub struct EditCosts{
+        pub substitution : u32,
+        pub insertion : u32,
+        pub deletion : u32,
+    }
+
+    impl EditCosts{
+        pub fn unit() -> EditCosts {
+            EditCosts{substitution : 1, insertion : 1, deletion : 1}
         }
+
+        #[inline]
+        pub fn is_unit(&self) -> bool {
+            *self == EditCosts::unit()
+        }
+
+        pub fn cheapest(&self) -> u32 {
+            min(self.substitution, min(self.insertion, self.deletion))
+        }
+
+        // the least an indel may cost, whichever string it is in (see swapped)
+        pub fn cheapest_indel(&self) -> u32 {
+            min(self.insertion, self.deletion)
+        }
+
+        // the costs with the roles of A and B exchanged
+        pub fn swapped(&self) -> EditCosts {
+            EditCosts{substitution : self.substitution, insertion : self.deletion, deletion : self.insertion}
+        }
+    }
+
+    /*
+    the format of the output file
+    Tsv : one solution per line with the columns described in the README
+    Gfa1, Gfa2 : the overlap graph, with an S line per input string and an edge per solution
+    Paf : one solution per line as the pairwise mapping format of minimap2
+    */
+    #[derive(Debug, PartialEq, Copy, Clone)]
+    pub enum OutputFormat{
+        Tsv,
+        Gfa1,
+        Gfa2,
+        Paf,
     }
 
-    pub static N_ALPH : &'static [u8] = b"ACGNT";
-    pub static ALPH : &'static [u8] = b"ACGT";
+    // IUPAC nucleotide ambiguity codes other than N itself
+    static IUPAC_AMBIGUOUS : &[u8] = b"RYSWKMBDHV";
+
+    /*
+    the symbols the input strings are made of.
+    complements : the complement of each symbol (in the same order) if the strings can be reverse complemented (-r)
+    wildcard : the symbol for an unknown one (ie: N). It counts as an error against any symbol, even itself,
+    and is left out of the index with --no_n
+    ambiguous : symbols that --normalise=mask replaces with the wildcard
+    */
+    #[derive(Debug, PartialEq, Clone)]
+    pub struct Alphabet{
+        pub name : String,
+        pub symbols : Vec<u8>,
+        pub complements : Option<Vec<u8>>,
+        pub wildcard : Option<u8>,
+        pub ambiguous : Vec<u8>,
+        with_wildcard : Vec<u8>,
+    }
+
+    impl Alphabet{
+        fn new(name : &str, symbols : &[u8], complements : Option<&[u8]>,
+               wildcard : Option<u8>, ambiguous : &[u8]) -> Alphabet {
+            // symbols are sorted along with their complements
+            let mut pairs : Vec<(u8, u8)> = symbols.iter().cloned()
+                .zip(complements.unwrap_or(symbols).iter().cloned())
+                .collect();
+            pairs.sort();
+            let symbols : Vec<u8> = pairs.iter().map(|p| p.0).collect();
+            let mut with_wildcard = symbols.clone();
+            with_wildcard.extend(wildcard);
+            with_wildcard.sort();
+            Alphabet{
+                name : name.to_owned(),
+                symbols,
+                complements : complements.map(|_| pairs.iter().map(|p| p.1).collect()),
+                wildcard,
+                ambiguous : ambiguous.to_vec(),
+                with_wildcard,
+            }
+        }
+
+        pub fn dna() -> Alphabet {
+            Alphabet::new("dna", b"ACGT", Some(b"TGCA"), Some(b'N'), IUPAC_AMBIGUOUS)
+        }
+
+        pub fn rna() -> Alphabet {
+            Alphabet::new("rna", b"ACGU", Some(b"UGCA"), Some(b'N'), IUPAC_AMBIGUOUS)
+        }
+
+        // the 20 standard amino acids. X is any amino acid, B, J and Z are ambiguous, O and U are rare ones
+        pub fn protein() -> Alphabet {
+            Alphabet::new("protein", b"ACDEFGHIKLMNPQRSTVWY", None, Some(b'X'), b"BJOUZ")
+        }
+
+        /*
+        one of the presets {dna, rna, protein}, or the given symbols, optionally followed by their complements
+        ie: "ABXY" or "ABXY:BAYX". User-defined alphabets have no wildcard.
+        */
+        pub fn parse(arg : &str) -> Result<Alphabet, String> {
+            match arg {
+                "dna" => return Ok(Alphabet::dna()),
+                "rna" => return Ok(Alphabet::rna()),
+                "protein" => return Ok(Alphabet::protein()),
+                _ => (),
+            }
+            let mut parts = arg.splitn(2, ':');
+            let symbols = parts.next().unwrap_or("").as_bytes();
+            let complements = parts.next().map(|c| c.as_bytes());
+            if symbols.is_empty() {
+                return Err("an alphabet needs at least one symbol.".to_owned());
+            }
+            for (i, c) in symbols.iter().enumerate() {
+                // the index sorts the separators '#' and '$' before every symbol
+                if !c.is_ascii_graphic() || *c <= b'$' || symbols[..i].contains(c) {
+                    return Err(format!("'{}' can't be a symbol of the alphabet. Symbols must be unique, \
+                                        printable and come after '$' (so none of '!', '\"', '#' and '$').", *c as char));
+                }
+            }
+            if let Some(complements) = complements {
+                let valid = complements.len() == symbols.len()
+                    && complements.iter().enumerate().all(|(i, c)| match symbols.iter().position(|s| s == c) {
+                        Some(j) => complements[j] == symbols[i],
+                        None => false,
+                    });
+                if !valid {
+                    return Err(format!("'{}' are not complements of the symbols '{}'. Each symbol needs one, \
+                                        and the complement of a complement is the symbol itself.",
+                                       String::from_utf8_lossy(complements), String::from_utf8_lossy(symbols)));
+                }
+            }
+            Ok(Alphabet::new(arg, symbols, complements, None, b""))
+        }
+
+        #[inline]
+        pub fn contains(&self, c : u8) -> bool {
+            self.symbols.contains(&c) || self.wildcard == Some(c)
+        }
+
+        #[inline]
+        pub fn is_wildcard(&self, c : u8) -> bool {
+            self.wildcard == Some(c)
+        }
+
+        pub fn has_complements(&self) -> bool {
+            self.complements.is_some()
+        }
+
+        // the wildcard is its own complement. Without complements, every symbol is
+        pub fn complement(&self, c : u8) -> u8 {
+            match (&self.complements, self.symbols.iter().position(|&s| s == c)) {
+                (Some(complements), Some(i)) => complements[i],
+                _ => c,
+            }
+        }
+
+        pub fn reverse_complement(&self, string : &[u8]) -> Vec<u8> {
+            string.iter().rev().map(|&c| self.complement(c)).collect()
+        }
+    }
 
 
     #[derive(Debug)]
@@ -213,24 +453,155 @@ pub mod run_config{
         pub thresh : i32,
 
         //optional
+        pub symmetric_thresh: bool, // both overlap lengths must reach thresh, not just the longer one
+        pub max_length_ratio: Option<f32>,
+        pub max_errors: Option<u32>,
         pub format_line: bool,
         pub greedy_output: bool,
         pub reversals : bool,
         pub inclusions : bool,
         pub edit_distance : bool,
+        pub costs : EditCosts,
         pub verbosity: u8,
         pub print: bool,
-        pub n_alphabet: bool,
+        pub n_alphabet: bool, // whether the wildcard of the alphabet is kept (N for nucleotides)
+        pub alphabet: Alphabet,
         pub track_progress: bool,
         pub worker_threads: usize,
+        pub min_quality: Option<u8>,
+        pub normalisation: Normalisation,
+        pub save_index: Option<String>,
+        pub load_index: Option<String>,
+        pub sa_sampling: usize,
+        pub occ_sampling: u32,
+        pub output_format: OutputFormat,
+        pub gfa_sequences: bool,
+        pub cigar: bool,
+        pub sort_buffer: Option<usize>,
+        pub temp_dir: Option<String>,
+        pub remove_contained: bool,
+        pub transitive_reduction: bool,
+        pub transitive_fuzz: usize,
+        pub best_overlaps: Option<usize>,
+        pub unitigs: Option<String>,
+        pub reference: Option<String>,
+        pub update: Option<String>,
+    }
+
+    /*
+    mirrors the defaults of the command line. Only the fields needed by the library are meaningful,
+    ie: input and output are left empty as they are only used by the binary
+    */
+    impl Default for Config{
+        fn default() -> Self {
+            Config{
+                input : String::new(),
+                output : String::new(),
+                err_rate : 0.0,
+                thresh : 1,
+
+                symmetric_thresh : false,
+                max_length_ratio : None,
+                max_errors : None,
+                format_line : false,
+                greedy_output : false,
+                reversals : false,
+                inclusions : false,
+                edit_distance : false,
+                costs : EditCosts::unit(),
+                verbosity : 0,
+                print : false,
+                n_alphabet : true,
+                alphabet : Alphabet::dna(),
+                track_progress : false,
+                worker_threads : max(1, num_cpus::get()-1),
+                min_quality : None,
+                normalisation : Normalisation::Upper,
+                save_index : None,
+                load_index : None,
+                sa_sampling : 1,
+                occ_sampling : 3,
+                output_format : OutputFormat::Tsv,
+                gfa_sequences : false,
+                cigar : false,
+                sort_buffer : None,
+                temp_dir : None,
+                remove_contained : false,
+                transitive_reduction : false,
+                transitive_fuzz : 10,
+                best_overlaps : None,
+                unitigs : None,
+                reference : None,
+                update : None,
+            }
+        }
     }
 
     impl Config{
-        pub fn alphabet(&self) -> &[u8]{
+        // whether anything that needs the complete solution set at once is enabled (filters, unitigs)
+        pub fn needs_all_solutions(&self) -> bool {
+            self.remove_contained || self.transitive_reduction || self.best_overlaps.is_some()
+                || self.unitigs.is_some()
+        }
+
+        // whether the strings of the input are searched for as queries, appended to another set of strings
+        pub fn appends_queries(&self) -> bool {
+            self.reference.is_some() || self.update.is_some()
+        }
+
+        // the file whose strings are indexed: the reference set if there is one
+        pub fn indexed_input(&self) -> &str {
+            match self.reference {
+                Some(ref reference) => reference,
+                None => &self.input,
+            }
+        }
+
+        // the most errors (K) a solution with an overlap of this length (the longer of the two) may have
+        pub fn error_limit(&self, overlap_len : usize) -> u32 {
+            let k_limit = (self.err_rate * (overlap_len as f32)).floor() as u32;
+            match self.max_errors {
+                Some(max_errors) => min(k_limit, max_errors),
+                None => k_limit,
+            }
+        }
+
+        // whether no solution may have any errors at all, so that the search needs no filters (see GeneratesCandidates::generate_exact_candidates)
+        pub fn is_exact(&self) -> bool {
+            self.err_rate == 0.0 || self.max_errors == Some(0)
+        }
+
+        /*
+        the most errors of any kind a solution may have, regardless of its length (see EditCosts).
+        the partition schemes of modes are told about this (see IsMode::get_block_lengths)
+        */
+        pub fn max_error_count(&self) -> Option<i32> {
+            self.max_errors.map(|k| (k / self.costs.cheapest()) as i32)
+        }
+
+        /*
+        whether an overlap with these lengths of the overlapping sections of A and B may be a solution.
+        The longer of the two must reach thresh (both with symmetric_thresh),
+        and the longer may be at most max_length_ratio times the shorter.
+        */
+        pub fn overlap_lengths_allowed(&self, overlap_a : usize, overlap_b : usize) -> bool {
+            let (shorter, longer) = (min(overlap_a, overlap_b), max(overlap_a, overlap_b));
+            let thresholded = if self.symmetric_thresh {shorter} else {longer};
+            if thresholded < self.thresh as usize {
+                return false;
+            }
+            match self.max_length_ratio {
+                Some(ratio) => longer as f32 <= shorter as f32 * ratio,
+                None => true,
+            }
+        }
+
+        // the symbols of the index, in sorted order
+        pub fn index_symbols(&self) -> &[u8]{
             if self.n_alphabet {
-                &N_ALPH
+                &self.alphabet.with_wildcard
             } else {
-                &ALPH
+                &self.alphabet.symbols
             }
         }
     }
@@ -4,17 +4,20 @@ the right solutions, using some predetermined inputs in th text_input directory.
 */
 #[cfg(test)]
 mod tests {
-    use std;
     use crate::search;
     use std::fs::File;
     use crate::useful::Orientation::{Normal, Reversed};
-    use crate::modes;
-    use crate::solve;
+    use crate::modes::{self, Mode};
+    use crate::{solve, solve_indexed, index, output, filter, string_graph, Index, Overlapper, ExternalSorter};
+    use crate::string_graph::StringGraph;
+    use bio::alphabets::dna::revcomp;
     use std::collections::HashSet;
+    use std::cmp::min;
     use crate::prepare;
-    use crate::structs::run_config::Config;
+    use crate::structs::run_config::{Config, Normalisation, OutputFormat, Alphabet, EditCosts};
     use crate::useful::Orientation;
     use std::io::{BufReader, BufRead};
+    use bio::data_structures::fmindex::Interval;
 
     #[derive (Eq, PartialEq, Hash, Debug)]
     struct GoodSolution{
@@ -32,7 +35,7 @@ mod tests {
     fn basic_mapping() {
         let config = Config{
             input  :        "./test_input/basic_mapping.fasta".to_owned(),
-            output  :       "./test_output/basic_mapping.txt".to_owned(),
+            output  :       output_path("basic_mapping.txt"),
             err_rate :      0.03,
             thresh :        8,
             worker_threads: 1,
@@ -45,6 +48,7 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         assert_eq!(2, maps.num_ids());
@@ -59,7 +63,7 @@ mod tests {
     fn ham() {
         let config = Config{
             input  :        "./test_input/ham.fasta".to_owned(),
-            output  :       "./test_output/ham.txt".to_owned(),
+            output  :       output_path("ham.txt"),
             err_rate :      0.02,
             thresh :        4,
             worker_threads: 1,
@@ -72,10 +76,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:5, ohb:10, ola:5, olb:5, err:0});
@@ -86,7 +91,7 @@ mod tests {
     fn edit() {
         let config = Config{
             input  :        "./test_input/edit.fasta".to_owned(),
-            output  :       "./test_output/edit.txt".to_owned(),
+            output  :       output_path("edit.txt"),
             err_rate :      0.2,
             thresh :        5,
             worker_threads: 1,
@@ -99,10 +104,65 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+        should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:7, ohb:7, ola:6, olb:7, err:1});
+        panic_if_solutions_missing(results, should_contain);
+    }
+
+    #[test]
+    fn overlap_length_limits() {
+        let mut config = Config{
+            input  :        "./test_input/edit.fasta".to_owned(),
+            output  :       output_path("edit_limits.txt"),
+            err_rate :      0.2,
+            thresh :        7,
+            worker_threads: 1,
+            edit_distance : true,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let expected = GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:7, ohb:7, ola:6, olb:7, err:1};
+        // (symmetric_thresh, max_length_ratio, whether the solution with lengths 6 and 7 is allowed)
+        for &(symmetric_thresh, max_length_ratio, allowed) in [(false, None, true), (true, None, false),
+                (false, Some(1.2), true), (false, Some(1.1), false)].iter() {
+            config.symmetric_thresh = symmetric_thresh;
+            config.max_length_ratio = max_length_ratio;
+            solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+            let results = read_output(&config.output);
+            assert_eq!(results.contains(&expected), allowed);
+            for s in results.iter() {
+                assert!(config.overlap_lengths_allowed(s.ola, s.olb));
+            }
+        }
+    }
+
+    #[test]
+    fn max_errors() {
+        let mut config = Config{
+            input  :        "./test_input/edit.fasta".to_owned(),
+            output  :       output_path("edit_max_errors.txt"),
+            err_rate :      0.2,
+            thresh :        5,
+            worker_threads: 1,
+            edit_distance : true,
+            n_alphabet:     false,
+            max_errors :    Some(0),
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        assert!(results.iter().all(|s| s.err == 0));
+        assert!(!results.iter().any(|s| s.ola == 6 && s.olb == 7));
+        config.max_errors = Some(1);
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:7, ohb:7, ola:6, olb:7, err:1});
@@ -113,7 +173,7 @@ mod tests {
     fn ham_rev() {
         let config = Config{
             input  :        "./test_input/ham_rev.fasta".to_owned(),
-            output  :       "./test_output/ham_rev.txt".to_owned(),
+            output  :       output_path("ham_rev.txt"),
             err_rate :      0.02,
             thresh :        5,
             worker_threads: 1,
@@ -126,10 +186,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Reversed, oha:8, ohb:8, ola:8, olb:8, err:0});
@@ -140,7 +201,7 @@ mod tests {
     fn ham_incl() {
         let config = Config{
             input  :        "./test_input/ham_incl.fasta".to_owned(),
-            output  :       "./test_output/ham_incl.txt".to_owned(),
+            output  :       output_path("ham_incl.txt"),
             err_rate :      0.02,
             thresh :        6,
             worker_threads: 1,
@@ -153,10 +214,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:4, ohb:-4, ola:8, olb:8, err:0});
@@ -167,7 +229,7 @@ mod tests {
     fn ham_no_n() {
         let config = Config{
             input  :        "./test_input/ham_no_n.fasta".to_owned(),
-            output  :       "./test_output/ham_no_n.txt".to_owned(),
+            output  :       output_path("ham_no_n.txt"),
             err_rate :      0.02,
             thresh :        5,
             worker_threads: 1,
@@ -180,10 +242,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:6, ohb:9, ola:6, olb:6, err:0});
@@ -194,7 +257,7 @@ mod tests {
     fn ham_rev_incl() {
         let config = Config{
             input  :        "./test_input/ham_rev_incl.fasta".to_owned(),
-            output  :       "./test_output/ham_rev_incl.txt".to_owned(),
+            output  :       output_path("ham_rev_incl.txt"),
             err_rate :      0.02,
             thresh :        5,
             worker_threads: 1,
@@ -207,10 +270,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Reversed, oha:5, ohb:-5, ola:5, olb:5, err:0});
@@ -221,7 +285,7 @@ mod tests {
     fn edit_rev() {
         let config = Config{
             input  :        "./test_input/edit_rev.fasta".to_owned(),
-            output  :       "./test_output/edit_rev.txt".to_owned(),
+            output  :       output_path("edit_rev.txt"),
             err_rate :      0.18,
             thresh :        7,
             worker_threads: 1,
@@ -234,10 +298,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Reversed, oha:4, ohb:8, ola:7, olb:6, err:1});
@@ -248,7 +313,7 @@ mod tests {
     fn edit_incl() {
         let config = Config{
             input  :        "./test_input/edit_incl.fasta".to_owned(),
-            output  :       "./test_output/edit_incl.txt".to_owned(),
+            output  :       output_path("edit_incl.txt"),
             err_rate :      0.17,
             thresh :        6,
             worker_threads: 1,
@@ -261,10 +326,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:4, ohb:-8, ola:7, olb:6, err:1});
@@ -275,7 +341,7 @@ mod tests {
     fn edit_rev_incl() {
         let config = Config{
             input  :        "./test_input/edit_rev_incl.fasta".to_owned(),
-            output  :       "./test_output/edit_rev_incl.txt".to_owned(),
+            output  :       output_path("edit_rev_incl.txt"),
             err_rate :      0.21,
             thresh :        5,
             worker_threads: 1,
@@ -288,10 +354,11 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Reversed, oha:5, ohb:-5, ola:5, olb:6, err:1});
@@ -302,7 +369,7 @@ mod tests {
     fn many_errors() {
         let config = Config{
             input  :        "./test_input/many_errors.fasta".to_owned(),
-            output  :       "./test_output/many_errors.txt".to_owned(),
+            output  :       output_path("many_errors.txt"),
             err_rate :      0.4,
             thresh :        8,
             worker_threads: 1,
@@ -315,32 +382,1123 @@ mod tests {
             print:          false,
             n_alphabet:     false,
             format_line:    false,
-
+            ..Config::default()
         };
         let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
         let mode = modes::default_mode();
-        solve(&config, &maps, mode);
+        solve(&config, &maps, mode).expect("Couldn't write output.");
         let results = read_output(&config.output);
         let mut should_contain : HashSet<GoodSolution> = HashSet::new();
         should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:0, ohb:0, ola:10, olb:10, err:4});
         panic_if_solutions_missing(results, should_contain);
     }
 
+    #[test]
+    fn ham_qual() {
+        let config = Config{
+            input  :        "./test_input/ham_qual.fastq".to_owned(),
+            output  :       output_path("ham_qual.txt"),
+            err_rate :      0.2,
+            thresh :        4,
+            worker_threads: 1,
+            n_alphabet:     true,
+            min_quality:    Some(20),
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        assert_eq!(maps.get_string(1)[12], b'N'); // stored backwards
+        let mut seq = b"ACGT".to_vec();
+        assert_eq!(prepare::mask_low_quality(&mut seq, b"I+II", 20, b'N').unwrap(), 1);
+        assert_eq!(&seq[..], &b"ANGT"[..]);
+        assert!(matches!(prepare::mask_low_quality(&mut seq, b"II", 20, b'N'), Err(crate::Error::Input(_))));
+        let mode = modes::default_mode();
+        solve(&config, &maps, mode).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+        should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:5, ohb:10, ola:5, olb:5, err:1});
+        panic_if_solutions_missing(results, should_contain);
+    }
+
+    #[test]
+    fn compressed_input() {
+        let config = Config{
+            err_rate :      0.2,
+            thresh :        4,
+            worker_threads: 1,
+            min_quality:    Some(20),
+            ..Config::default()
+        };
+        let plain = prepare::read_and_prepare("./test_input/ham.fasta", &config).expect("Couldn't interpret data.");
+        let gzipped = prepare::read_and_prepare("./test_input/ham_bgz.fasta.gz", &config).expect("Couldn't interpret data.");
+        assert_eq!(plain.text, gzipped.text);
+        assert_eq!(plain.id2name_vec, gzipped.id2name_vec);
+
+        let plain = prepare::read_and_prepare("./test_input/ham_qual.fastq", &config).expect("Couldn't interpret data.");
+        let zstd = prepare::read_and_prepare("./test_input/ham_qual.fastq.zst", &config).expect("Couldn't interpret data.");
+        assert_eq!(plain.text, zstd.text);
+        assert_eq!(plain.id2name_vec, zstd.id2name_vec);
+    }
+
+    #[test]
+    fn normalisation() {
+        let mut config = Config{
+            input  :        "./test_input/iupac.fasta".to_owned(),
+            output  :       output_path("iupac.txt"),
+            err_rate :      0.02,
+            thresh :        4,
+            worker_threads: 1,
+            normalisation:  Normalisation::Strict,
+            ..Config::default()
+        };
+        let err = prepare::read_and_prepare(&config.input, &config).unwrap_err();
+        assert!(err.to_string().contains("'x'"));
+        config.normalisation = Normalisation::Upper;
+        let err = prepare::read_and_prepare(&config.input, &config).unwrap_err();
+        assert!(err.to_string().contains("'y'"));
+
+        for &reversals in [false, true].iter() {
+            config.normalisation = Normalisation::Mask;
+            config.reversals = reversals;
+            let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            assert!(maps.text.iter().all(|c| b"ACGTN$#".contains(c)));
+            solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+            let results = read_output(&config.output);
+            let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+            should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:5, ohb:10, ola:5, olb:5, err:0});
+            panic_if_solutions_missing(results, should_contain);
+        }
+    }
+
+    #[test]
+    fn alphabets() {
+        assert!(Alphabet::parse("ab:ba").unwrap().has_complements());
+        assert!(!Alphabet::parse("abc").unwrap().has_complements());
+        assert!(Alphabet::parse("aab").is_err());
+        assert!(Alphabet::parse("a$").is_err());
+        assert!(Alphabet::parse("a!").is_err());
+        assert!(Alphabet::parse("\"a").is_err());
+        assert!(Alphabet::parse("abc:bca").is_err());
+
+        let mut config = Config{
+            input  :        "./test_input/rna.fasta".to_owned(),
+            output  :       output_path("rna.txt"),
+            err_rate :      0.0,
+            thresh :        5,
+            worker_threads: 1,
+            ..Config::default()
+        };
+        let err = prepare::read_and_prepare(&config.input, &config).unwrap_err();
+        assert!(err.to_string().contains("'U'"));
+        config.alphabet = Alphabet::rna();
+        for &reversals in [false, true].iter() {
+            config.reversals = reversals;
+            let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            assert!(maps.text.iter().all(|c| b"ACGU$#".contains(c)));
+            solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+            let results = read_output(&config.output);
+            let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+            should_contain.insert(GoodSolution{a_nm:"r1".to_owned(), b_nm:"r2".to_owned(), or:Normal, oha:5, ohb:5, ola:5, olb:5, err:0});
+            panic_if_solutions_missing(results, should_contain);
+        }
+
+        let config = Config{
+            input  :        "./test_input/protein.fasta".to_owned(),
+            output  :       output_path("protein.txt"),
+            err_rate :      0.0,
+            thresh :        5,
+            worker_threads: 1,
+            alphabet :      Alphabet::protein(),
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+        should_contain.insert(GoodSolution{a_nm:"p1".to_owned(), b_nm:"p2".to_owned(), or:Normal, oha:5, ohb:5, ola:7, olb:7, err:0});
+        panic_if_solutions_missing(results, should_contain);
+    }
+
+    #[test]
+    fn index_persistence() {
+        let mut config = Config{
+            input  :        "./test_input/ham_rev.fasta".to_owned(),
+            err_rate :      0.02,
+            thresh :        5,
+            worker_threads: 1,
+            reversals :     true,
+            ..Config::default()
+        };
+        let filename = std::env::temp_dir().join("rust_overlaps_index_persistence.idx");
+        let filename = filename.to_str().unwrap();
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let built = Index::build(&maps, &config);
+        index::save(filename, &maps, &built, &config).expect("Couldn't save index.");
+
+        let (loaded_maps, loaded) = index::load(filename, &config).expect("Couldn't load index.");
+        assert_eq!(maps.text, loaded_maps.text);
+        assert_eq!(maps.id2name_vec, loaded_maps.id2name_vec);
+        assert_eq!(maps.indexes, loaded_maps.indexes);
+        assert_eq!(built.sa, loaded.sa);
+        assert_eq!(built.bwt, loaded.bwt);
+        assert_eq!(built.less, loaded.less);
+        let solutions = Overlapper::new(&config, &maps).index(&built).solve();
+        let loaded_solutions = Overlapper::new(&config, &loaded_maps).index(&loaded).solve();
+        assert!(!solutions.is_empty());
+        assert_eq!(solutions, loaded_solutions);
+
+        config.reversals = false;
+        assert!(index::load(filename, &config).is_err());
+        config.reversals = true;
+        config.n_alphabet = false;
+        assert!(index::load(filename, &config).is_err());
+        config.n_alphabet = true;
+        // the same symbols as DNA (with its wildcard), but other complements
+        config.alphabet = Alphabet::parse("ACGTN:TGCAN").unwrap();
+        assert_eq!(config.index_symbols(), &b"ACGNT"[..]);
+        assert!(index::load(filename, &config).is_err());
+        config.alphabet = Alphabet::dna();
+
+        let bytes = std::fs::read(filename).unwrap();
+        let corrupt = |at : usize, value : usize| {
+            let mut corrupt_bytes = bytes.clone();
+            corrupt_bytes[at..at+8].copy_from_slice(&(value as u64).to_le_bytes());
+            std::fs::write(filename, &corrupt_bytes).unwrap();
+            let error = index::load(filename, &config).err().expect("Loaded a corrupt index.");
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        };
+        // a length beyond the end of the file (that of the alphabet, after the magic, version and reversals)
+        corrupt(24, 1 << 40);
+        // the id indexes come right before the length of the text, the last one of them is that of the last id
+        let text_at = bytes.windows(maps.text.len()).position(|w| w == &maps.text[..]).unwrap();
+        let last_id_at = text_at - 16;
+        let last_index = maps.index_for(maps.num_ids() - 1);
+        let other_index = maps.index_for(0);
+        for &index in [last_index + 1, other_index, maps.text.len(), 0].iter() {
+            corrupt(last_id_at, index);
+        }
+        // less is the last entry of the file, one entry short of what the alphabet needs
+        corrupt(bytes.len() - 8 * built.less.len() - 8, built.less.len() - 1);
+        std::fs::write(filename, &bytes).unwrap();
+        assert!(index::load(filename, &config).is_ok());
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn exact_search() {
+        for &(input, edit_distance) in [("ham", false), ("ham_incl", false), ("ham_rev_incl", false),
+                                        ("edit_rev_incl", true), ("layout", true)].iter() {
+            let mut config = mode_test_config(input, 0.1, 4, edit_distance);
+            let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            let mut filtered = Overlapper::new(&config, &maps).solve();
+            filtered.retain(|s| s.errors == 0);
+            config.err_rate = 0.0;
+            assert!(config.is_exact());
+            let exact = Overlapper::new(&config, &maps).solve();
+            assert_eq!(filtered, exact);
+        }
+    }
+
+    #[test]
+    fn bidirectional_index() {
+        use crate::index::{ReverseIndex, BidirectionalIndex};
+        let config = Config{
+            input  :        "./test_input/layout.fasta".to_owned(),
+            reversals :     true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let index = Index::build(&maps, &config);
+        let reverse = ReverseIndex::build(&maps, &index, &config);
+        let bi = BidirectionalIndex::new(&index, &reverse, &config);
+        let string = maps.get_string(0);
+        for start in 0..string.len() {
+            for end in (start+1)..(string.len()+1) {
+                // backward search only
+                let mut backward = bi.full_interval();
+                for &c in string[start..end].iter().rev() {
+                    backward = bi.extend_left(&backward, c);
+                }
+                assert!(!backward.is_empty());
+                // outwards from the middle, to the right and then to the left
+                let middle = (start + end) / 2;
+                let mut outward = bi.full_interval();
+                for &c in string[middle..end].iter() {
+                    outward = bi.extend_right(&outward, c);
+                }
+                for &c in string[start..middle].iter().rev() {
+                    outward = bi.extend_left(&outward, c);
+                }
+                assert_eq!(backward, outward);
+                if start == 0 {
+                    // a prefix of the string in the text, so preceded by '$'
+                    assert!(!bi.extend_left(&backward, b'$').is_empty());
+                }
+            }
+        }
+    }
+
+    /*
+    matches each block of a filter but the first (all of length 1, allowing any errors) before the first,
+    extending each search backwards once.
+    */
+    #[derive(Debug)]
+    struct MiddleOutMode;
+
+    impl std::fmt::Display for MiddleOutMode {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "middle out")
+        }
+    }
+
+    #[allow(unused_variables)]
+    impl modes::IsMode for MiddleOutMode {
+        fn filter_func(&self, completed_blocks : i32, patt_blocks : i32, blind_blocks : i32) -> i32 {
+            patt_blocks
+        }
+        fn get_block_lengths(&self, patt_len : i32, err_rate : f32, thresh : i32, max_errors : Option<i32>) -> Vec<i32> {
+            vec![1; patt_len as usize]
+        }
+        fn candidate_condition(&self, generous_overlap_len : i32, completed_blocks : i32, thresh : i32, errors : i32) -> bool {
+            generous_overlap_len >= thresh && completed_blocks > 0
+        }
+        fn get_fewest_suff_blocks(&self) -> i32 {
+            2
+        }
+        fn get_guaranteed_extra_blocks(&self) -> i32 {
+            1
+        }
+        fn block_order(&self, search : i32, patt_blocks : i32, blind_blocks : i32) -> Vec<i32> {
+            let mut order = vec![blind_blocks + 1, blind_blocks];
+            order.extend((blind_blocks + 2)..patt_blocks);
+            order
+        }
+        fn bidirectional(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn bidirectional_search() {
+        assert_eq!(modes::block_directions(&[3, 2, 4, 1], 5, 1),
+                   vec![modes::Direction::Forwards, modes::Direction::Backwards, modes::Direction::Forwards, modes::Direction::Backwards]);
+        for &(input, edit_distance) in [("edit", true), ("edit_rev_incl", true), ("ham_rev_incl", false),
+                                        ("layout", true), ("layout", false)].iter() {
+            let config = mode_test_config(input, 0.21, 4, edit_distance);
+            // compared with valimaki, as the default mode misses some edit distance overlaps of layout.fasta
+            assert_same_solutions(&config, modes::get_mode("valimaki").unwrap(), Box::new(MiddleOutMode));
+        }
+    }
+
+    #[test]
+    fn search_scheme() {
+        use crate::modes::scheme::SchemeMode;
+        // misses an error in each of parts 0 and 2
+        let uncovering = "parts = 3\nerrors = 2\n[[search]]\norder = [0, 1, 2]\nlower = [0, 0, 0]\nupper = [0, 2, 2]\n\
+                          [[search]]\norder = [2, 1, 0]\nlower = [0, 0, 0]\nupper = [0, 0, 2]";
+        assert!(SchemeMode::parse("uncovering", uncovering).unwrap_err().contains("1, 0, 1"));
+        let not_adjacent = "parts = 3\nerrors = 0\n[[search]]\norder = [0, 2, 1]\nlower = [0, 0, 0]\nupper = [0, 0, 0]";
+        assert!(SchemeMode::parse("not_adjacent", not_adjacent).is_err());
+        assert!(SchemeMode::parse("no_searches", "parts = 3\nerrors = 0").is_err());
+        // the rest of the pattern may hold the error the second search doesn't allow
+        let capped = "parts = 1\nerrors = 1\n[[search]]\norder = [0]\nlower = [0]\nupper = [1]\n\
+                      [[search]]\norder = [0]\nlower = [0]\nupper = [0]";
+        assert!(SchemeMode::parse("capped", capped).unwrap_err().contains("last upper bound of search 1"));
+        // any TOML: inline tables, arrays over several lines, '#' in a string (of a key that isn't the scheme's)
+        let inline = "parts = 1\nerrors = 1\nsearch = [\n  {order = [0], lower = [0], upper = [1]},  # K\n  \
+                      {order = [0], lower = [1], upper = [1]},\n]";
+        SchemeMode::parse("inline", inline).unwrap();
+        let unknown_key = "parts = 1\nerrors = 0\nname = \"#1\"\n[[search]]\norder = [0]\nlower = [0]\nupper = [0]";
+        assert!(SchemeMode::parse("unknown_key", unknown_key).unwrap_err().contains("name"));
+
+        // scheme_k2 has 3 parts, which need THRESH-K symbols at least, and K at most 2
+        let scheme = modes::get_mode("scheme_./test_input/scheme_k2.toml").unwrap();
+        for &(thresh, max_errors) in [(4, Some(2)), (6, Some(3)), (6, None)].iter() {
+            let config = Config{thresh, err_rate : 0.21, max_errors, ..Config::default()};
+            assert!(matches!(modes::check_mode(scheme.as_ref(), &config), Err(crate::Error::Parameter(_))));
+        }
+        let config = Config{thresh : 5, err_rate : 0.21, max_errors : Some(2), ..Config::default()};
+        assert!(modes::check_mode(scheme.as_ref(), &config).is_ok());
+
+        for &(input, edit_distance) in [("edit_rev_incl", true), ("ham_rev_incl", false),
+                                        ("layout", true), ("layout", false)].iter() {
+            let config = Config{max_errors : Some(2), ..mode_test_config(input, 0.21, 6, edit_distance)};
+            assert_same_solutions(&config, modes::default_mode(), modes::get_mode("scheme_./test_input/scheme_k2.toml").unwrap());
+        }
+    }
+
+    #[test]
+    fn auto_mode() {
+        for &(err_rate, edit_distance) in [(0.21, true), (0.1, false), (0.0, true)].iter() {
+            let config = mode_test_config("layout", err_rate, 4, edit_distance);
+            let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            let mode = modes::auto::select(&config, &maps, &Index::build(&maps, &config));
+            assert!(!mode.bidirectional());
+            assert_same_solutions(&config, modes::default_mode(), mode);
+        }
+    }
+
+    #[test]
+    fn sampled_index() {
+        let mut config = Config{
+            input  :        "./test_input/edit_rev_incl.fasta".to_owned(),
+            err_rate :      0.21,
+            thresh :        5,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance : true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let full = Index::build(&maps, &config);
+        let solutions = Overlapper::new(&config, &maps).index(&full).solve();
+        assert!(!solutions.is_empty());
+        let whole_text = Interval{lower : 0, upper : maps.text.len()};
+        let positions = full.locate(&whole_text);
+
+        for &(sa_sampling, occ_sampling) in [(2, 1), (5, 3), (8, 16), (100, 64)].iter() {
+            config.sa_sampling = sa_sampling;
+            config.occ_sampling = occ_sampling;
+            let sampled = Index::build(&maps, &config);
+            assert_eq!(sampled.sa.rate(), sa_sampling);
+            assert_eq!(positions, sampled.locate(&whole_text));
+            let sampled_solutions = Overlapper::new(&config, &maps).index(&sampled).solve();
+            assert_eq!(solutions, sampled_solutions);
+        }
+
+        let filename = std::env::temp_dir().join("rust_overlaps_sampled_index.idx");
+        let filename = filename.to_str().unwrap();
+        let sampled = Index::build(&maps, &config);
+        index::save(filename, &maps, &sampled, &config).expect("Couldn't save index.");
+        let (_, loaded) = index::load(filename, &config).expect("Couldn't load index.");
+        assert_eq!(sampled.sa, loaded.sa);
+        assert_eq!(positions, loaded.locate(&whole_text));
+
+        // one mark more or less than there are samples. the last word of marks is followed by the bwt and less
+        let mut bytes = std::fs::read(filename).unwrap();
+        let last_mark = bytes.len() - (8 + 8 * sampled.less.len()) - (8 + sampled.bwt.len()) - 8;
+        bytes[last_mark] ^= 1;
+        std::fs::write(filename, &bytes).unwrap();
+        let error = index::load(filename, &config).err().expect("Loaded a corrupt index.");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    // where a test writes the output file of the given name: the system's temporary directory, not the repository
+    fn output_path(name : &str) -> String {
+        let dir = std::env::temp_dir().join("rust_overlaps_tests");
+        std::fs::create_dir_all(&dir).expect("Couldn't create the test output directory");
+        dir.join(name).to_str().unwrap().to_owned()
+    }
+
+    // a run over the test input of the given name, with reversals and inclusions, as the mode comparisons use
+    fn mode_test_config(input : &str, err_rate : f32, thresh : i32, edit_distance : bool) -> Config {
+        Config{
+            input  :        format!("./test_input/{}.fasta", input),
+            err_rate,
+            thresh,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance,
+            ..Config::default()
+        }
+    }
+
+    // asserts that the mode finds the same solutions as the expected one for the run
+    fn assert_same_solutions(config : &Config, expected : Mode, mode : Mode) {
+        let maps = prepare::read_and_prepare(&config.input, config).expect("Couldn't interpret data.");
+        let names = (expected.to_string(), mode.to_string());
+        let expected = Overlapper::new(config, &maps).mode(expected).unwrap().solve();
+        let solutions = Overlapper::new(config, &maps).mode(mode).unwrap().solve();
+        assert_eq!(expected, solutions, "{} and {} disagree on {}", names.0, names.1, config.input);
+    }
+
+    fn read_lines(filename : &str) -> Vec<String> {
+        let f = File::open(filename).expect("Couldn't open output file");
+        BufReader::new(&f).lines().map(|l| l.unwrap()).collect()
+    }
+
+    #[test]
+    fn gfa_output() {
+        let mut config = Config{
+            input  :        "./test_input/ham_rev_incl.fasta".to_owned(),
+            output  :       output_path("ham_rev_incl.gfa"),
+            err_rate :      0.0,
+            thresh :        5,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            output_format : OutputFormat::Gfa1,
+            gfa_sequences : true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![
+            "H\tVN:Z:1.0",
+            "S\tx\tGGGGGTTTTTGGGGG\tLN:i:15",
+            "S\ty\tAAAAA\tLN:i:5",
+            "C\tx\t+\ty\t-\t5\t5M\tNM:i:0",
+        ]);
+
+        config.output = output_path("ham_rev_incl.gfa2");
+        config.output_format = OutputFormat::Gfa2;
+        config.gfa_sequences = false;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![
+            "H\tVN:Z:2.0",
+            "S\tx\t15\t*",
+            "S\ty\t5\t*",
+            "E\t*\tx+\ty-\t5\t10\t0\t5$\t5M\tNM:i:0",
+        ]);
+
+        // overlaps of differing lengths (edit distance) and a dovetail
+        let config = Config{
+            input  :        "./test_input/edit_rev.fasta".to_owned(),
+            output  :       output_path("edit_rev.gfa"),
+            err_rate :      0.2,
+            thresh :        5,
+            worker_threads: 1,
+            reversals :     true,
+            edit_distance : true,
+            n_alphabet:     false,
+            output_format : OutputFormat::Gfa1,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let lines = read_lines(&config.output);
+        assert!(lines.contains(&"L\tx\t+\ty\t-\t6M1D\tNM:i:1".to_owned()));
+    }
+
+    #[test]
+    fn paf_output() {
+        let mut config = Config{
+            input  :        "./test_input/ham.fasta".to_owned(),
+            output  :       output_path("ham.paf"),
+            err_rate :      0.02,
+            thresh :        4,
+            worker_threads: 1,
+            n_alphabet:     false,
+            output_format : OutputFormat::Paf,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec!["x\t10\t5\t10\t+\ty\t15\t0\t5\t5\t5\t255\tNM:i:0"]);
+
+        // inverted inclusion: the target interval is on y as it is in the input
+        config.input = "./test_input/ham_rev_incl.fasta".to_owned();
+        config.output = output_path("ham_rev_incl.paf");
+        config.err_rate = 0.0;
+        config.thresh = 5;
+        config.reversals = true;
+        config.inclusions = true;
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec!["x\t15\t5\t10\t-\ty\t5\t0\t5\t5\t5\t255\tNM:i:0"]);
+
+        // inverted overlap of the end of x with the end of y
+        config.input = "./test_input/edit_rev.fasta".to_owned();
+        config.output = output_path("edit_rev.paf");
+        config.err_rate = 0.2;
+        config.inclusions = false;
+        config.edit_distance = true;
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let lines = read_lines(&config.output);
+        assert!(lines.contains(&"x\t11\t4\t11\t-\ty\t14\t8\t14\t6\t7\t255\tNM:i:1".to_owned()));
+
+        // the alignments have y as the reference, on its input strand. the T of x is missing from y, the C of y from x
+        config.cigar = true;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let lines = read_lines(&config.output);
+        assert!(lines.contains(&"x\t11\t4\t11\t-\ty\t14\t8\t14\t6\t7\t255\tNM:i:1\tcg:Z:2M1I4M".to_owned()));
+        config.input = "./test_input/edit.fasta".to_owned();
+        config.output = output_path("edit.paf");
+        config.reversals = false;
+        config.err_rate = 0.3;
+        config.costs = EditCosts{substitution : 1, insertion : 2, deletion : 1};
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let lines = read_lines(&config.output);
+        // K is 2, as the insertion costs 2, but it is a single edit
+        assert!(lines.contains(&"x\t13\t7\t13\t+\ty\t14\t0\t7\t6\t7\t255\tNM:i:1\tcg:Z:3M1D3M".to_owned()));
+        // without the alignment the edits aren't known
+        config.cigar = false;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert!(read_lines(&config.output).contains(&"x\t13\t7\t13\t+\ty\t14\t0\t7\t5\t7\t255".to_owned()));
+    }
+
+    #[test]
+    fn cigar() {
+        for &(input, edit_distance) in [("edit", true), ("edit_rev", true), ("edit_incl", true),
+                                        ("edit_rev_incl", true), ("ham_rev_incl", false)].iter() {
+            let config = Config{
+                input  :        format!("./test_input/{}.fasta", input),
+                err_rate :      0.21,
+                thresh :        5,
+                worker_threads: 1,
+                reversals :     true,
+                inclusions :    true,
+                edit_distance,
+                cigar :         true,
+                ..Config::default()
+            };
+            let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            let solutions = Overlapper::new(&config, &maps).solve();
+            assert!(!solutions.is_empty());
+            for s in solutions.iter() {
+                // replay the alignment over the overlapping sections as they are oriented in the solution
+                let spans = output::OverlapSpans::of(s, &maps);
+                let mut a : Vec<u8> = maps.get_string(s.id_a).to_vec();
+                let mut b : Vec<u8> = maps.get_string(s.id_b).to_vec();
+                a.reverse();
+                b.reverse();
+                let (mut a_pos, mut b_pos, mut errors) = (spans.a_start, spans.b_start, 0);
+                let cigar = s.cigar.as_ref().unwrap();
+                assert_eq!(cigar.first().unwrap().1, b'M');
+                assert_eq!(cigar.last().unwrap().1, b'M');
+                for &(len, op) in cigar.iter() {
+                    for _ in 0..len {
+                        match op {
+                            b'M' => {
+                                if a[a_pos] != b[b_pos] {errors += 1;}
+                                a_pos += 1;
+                                b_pos += 1;
+                            },
+                            b'I' => {b_pos += 1; errors += 1;},
+                            b'D' => {a_pos += 1; errors += 1;},
+                            _ => panic!("unexpected CIGAR operation"),
+                        }
+                    }
+                }
+                assert_eq!((a_pos, b_pos), (spans.a_end, spans.b_end));
+                assert_eq!(errors, s.errors);
+            }
+        }
+
+        let config = Config{
+            input  :        "./test_input/edit.fasta".to_owned(),
+            output  :       output_path("edit_cigar.txt"),
+            err_rate :      0.2,
+            thresh :        5,
+            worker_threads: 1,
+            edit_distance : true,
+            n_alphabet:     false,
+            cigar :         true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert!(read_lines(&config.output).contains(&"x\ty\tN\t7\t7\t6\t7\t1\t3M1I3M".to_owned()));
+    }
+
+    #[test]
+    fn overlap_spans() {
+        let config = Config{
+            input  :        "./test_input/edit_rev_incl.fasta".to_owned(),
+            err_rate :      0.21,
+            thresh :        5,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance : true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let solutions = Overlapper::new(&config, &maps).solve();
+        assert!(!solutions.is_empty());
+        for s in solutions.iter() {
+            // what is left of each string after its overlap agrees with OHB
+            let spans = output::OverlapSpans::of(s, &maps);
+            assert_eq!(spans.a_len - spans.a_end, std::cmp::max(0, -s.overhang_right_b) as usize);
+            assert_eq!(spans.b_len - spans.b_end, std::cmp::max(0, s.overhang_right_b) as usize);
+            let (b_start, b_end) = spans.b_forward(&s.orientation);
+            assert_eq!(b_end - b_start, s.overlap_b);
+        }
+    }
+
+    #[test]
+    fn external_sort() {
+        let mut config = Config{
+            input  :        "./test_input/small_dataset.fasta".to_owned(),
+            output  :       output_path("small_dataset.txt"),
+            err_rate :      0.25,
+            thresh :        4,
+            worker_threads: 2,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance : true,
+            cigar :         true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let in_memory = read_lines(&config.output);
+        assert!(in_memory.len() > 20);
+
+        config.output = output_path("small_dataset_external.txt");
+        for &capacity in [1, 7, 1000000].iter() {
+            config.sort_buffer = Some(capacity);
+            solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+            assert_eq!(in_memory, read_lines(&config.output));
+        }
+
+        // duplicates within and across runs are merged
+        let solutions = Overlapper::new(&config, &maps).solve();
+        let mut sorter = ExternalSorter::new(&maps, &config, 3);
+        for s in solutions.iter().rev().chain(solutions.iter()) {
+            sorter.push(s.clone()).unwrap();
+        }
+        assert!(sorter.num_runs() > 1);
+        let mut merged = vec![];
+        let written = sorter.finish(|s| {merged.push(s.clone()); Ok(())}).unwrap();
+        assert_eq!(written, solutions.len());
+        assert_eq!(merged, solutions);
+        for (m, s) in merged.iter().zip(solutions.iter()) {
+            assert_eq!(m.errors, s.errors);
+            assert_eq!(m.cigar, s.cigar);
+        }
+
+        // more runs than are merged at once take several passes. no run files are left, finished or not
+        let dir = std::env::temp_dir().join(format!("rust_overlaps_external_sort_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        config.temp_dir = Some(dir.to_str().unwrap().to_owned());
+        let mut sorter = ExternalSorter::new(&maps, &config, 1).fan_in(3);
+        for s in solutions.iter().rev().chain(solutions.iter()) {
+            sorter.push(s.clone()).unwrap();
+        }
+        assert!(sorter.num_runs() > 9);
+        let mut merged = vec![];
+        assert_eq!(sorter.finish(|s| {merged.push(s.clone()); Ok(())}).unwrap(), solutions.len());
+        assert_eq!(merged, solutions);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let mut sorter = ExternalSorter::new(&maps, &config, 2);
+        for s in solutions.iter() {
+            sorter.push(s.clone()).unwrap();
+        }
+        assert!(std::fs::read_dir(&dir).unwrap().count() > 0);
+        drop(sorter);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn filters() {
+        // r1, r2 and r3 tile a sequence (r2 reversed), c is included in r1 and r2
+        let mut config = Config{
+            input  :        "./test_input/layout.fasta".to_owned(),
+            output  :       output_path("layout.txt"),
+            err_rate :      0.0,
+            thresh :        8,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let r1_r2 = "r1\tr2\tI\t6\t6\t14\t14\t0".to_owned();
+        let r1_r3 = "r1\tr3\tN\t12\t12\t8\t8\t0".to_owned();
+        let r2_r3 = "r2\tr3\tI\t-6\t-6\t14\t14\t0".to_owned();
+        let c_r1 = "c\tr1\tN\t-8\t2\t10\t10\t0".to_owned();
+        let c_r2 = "c\tr2\tI\t-2\t8\t10\t10\t0".to_owned();
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![c_r1.clone(), c_r2.clone(), r1_r2.clone(), r1_r3.clone(), r2_r3.clone()]);
+
+        config.remove_contained = true;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![r1_r2.clone(), r1_r3.clone(), r2_r3.clone()]);
+
+        config.transitive_reduction = true;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![r1_r2.clone(), r2_r3.clone()]);
+
+        // overlaps that don't add up are not transitive
+        config.transitive_fuzz = 0;
+        let mut solutions = Overlapper::new(&config, &maps).solve();
+        for s in solutions.iter_mut() {
+            if maps.get_name_for(s.id_b) == "r3" && s.orientation == Normal {
+                s.overhang_left_a += 1;
+                s.overhang_right_b += 1;
+            }
+        }
+        assert_eq!(filter::apply(solutions, &maps, &config).len(), 3);
+
+        config.remove_contained = false;
+        config.transitive_reduction = false;
+        config.best_overlaps = Some(1);
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![c_r1, c_r2, r1_r2, r2_r3]);
+    }
+
+    #[test]
+    fn unitigs() {
+        // r1, r2 (reversed) and r3 spell a single unitig. c is included and left out
+        let mut config = Config{
+            input  :        "./test_input/layout.fasta".to_owned(),
+            output  :       output_path("layout_unitigs.txt"),
+            err_rate :      0.0,
+            thresh :        8,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            n_alphabet:     false,
+            unitigs :       Some(output_path("layout_unitigs")),
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let solutions = Overlapper::new(&config, &maps).solve();
+        let graph = StringGraph::build(&solutions, &maps, &config);
+        let unitigs = graph.unitigs();
+        assert_eq!(unitigs.len(), 1);
+        let sequence = string_graph::unitig_sequence(&unitigs[0], &maps, &config);
+        let expected = b"GCTAAAGACAATTACATAACATACACGTCAGC".to_vec();
+        assert!(sequence == expected || sequence == revcomp(&expected));
+
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&output_path("layout_unitigs.fasta")), vec![
+            ">utg1 LN:i:32 reads=r1+,r2-,r3+".to_owned(),
+            "GCTAAAGACAATTACATAACATACACGTCAGC".to_owned(),
+        ]);
+        assert_eq!(read_lines(&output_path("layout_unitigs.gfa")).len(), 2);
+
+        // without reversals r2 can't be joined, and r1 and r3 only overlap each other
+        config.reversals = false;
+        config.unitigs = None;
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let solutions = Overlapper::new(&config, &maps).solve();
+        let unitigs = StringGraph::build(&solutions, &maps, &config).unitigs();
+        assert_eq!(unitigs.len(), 2);
+        assert_eq!(string_graph::unitig_sequence(&unitigs[0], &maps, &config), expected);
+        assert_eq!(string_graph::unitig_sequence(&unitigs[1], &maps, &config), b"GTGTATGTTATGTAATTGTC".to_vec());
+    }
+
+    #[test]
+    fn reference_set() {
+        // layout.fasta split into queries (r2, c) and references (r1, r3)
+        let config = Config{
+            input  :        "./test_input/layout_queries.fasta".to_owned(),
+            output  :       output_path("layout_reference.txt"),
+            reference :     Some("./test_input/layout_reference.fasta".to_owned()),
+            err_rate :      0.0,
+            thresh :        8,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        let references = prepare::read_and_prepare(config.indexed_input(), &config).expect("Couldn't interpret data.");
+        let reference_index = Index::build(&references, &config);
+        let reference_text_len = references.text.len();
+        let maps = prepare::read_and_append_queries(references, &config.input, &config).expect("Couldn't interpret data.");
+        assert_eq!(maps.query_ids(), 4..8);
+        let queries = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        assert!(matches!(prepare::append_queries(maps, queries, &config), Err(crate::Error::Parameter(_))));
+        let references = prepare::read_and_prepare(config.indexed_input(), &config).expect("Couldn't interpret data.");
+        let maps = prepare::read_and_append_queries(references, &config.input, &config).expect("Couldn't interpret data.");
+        assert_eq!(maps.get_name_for(4), "r2");
+        assert_eq!(maps.text_of(&maps.reference_ids()).1.len(), reference_text_len);
+        assert_eq!(Index::build(&maps, &config).bwt, reference_index.bwt);
+
+        // all overlaps between the two sets, none within either (c-r2, r1-r3)
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![
+            "c\tr1\tN\t-8\t2\t10\t10\t0".to_owned(),
+            "r1\tr2\tI\t6\t6\t14\t14\t0".to_owned(),
+            "r2\tr3\tI\t-6\t-6\t14\t14\t0".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn reference_set_forwards() {
+        // without reversals, overlaps from the end of a query (q1) to the start of a reference string (r1) are found too
+        let config = Config{
+            input  :        "./test_input/layout_queries_forwards.fasta".to_owned(),
+            output  :       output_path("layout_reference_forwards.txt"),
+            reference :     Some("./test_input/layout_reference.fasta".to_owned()),
+            err_rate :      0.0,
+            thresh :        8,
+            worker_threads: 1,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        let references = prepare::read_and_prepare(config.indexed_input(), &config).expect("Couldn't interpret data.");
+        let maps = prepare::read_and_append_queries(references, &config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        assert_eq!(read_lines(&config.output), vec![
+            "q1\tr1\tN\t6\t8\t12\t12\t0".to_owned(),
+            "q2\tr3\tN\t-11\t-6\t9\t9\t0".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn update() {
+        let mut config = Config{
+            input  :        "./test_input/update_new.fasta".to_owned(),
+            output  :       output_path("update.txt"),
+            update :        Some(output_path("update_previous.txt")),
+            err_rate :      0.05,
+            thresh :        20,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance : true,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        for &(reversals, inclusions) in [(true, true), (true, false), (false, true)].iter() {
+            config.reversals = reversals;
+            config.inclusions = inclusions;
+            let previous = prepare::read_and_prepare("./test_input/update_previous.fasta", &config).expect("Couldn't interpret data.");
+            let previous_solutions = Overlapper::new(&config, &previous).solve();
+            let mut buf = File::create(config.update.as_ref().unwrap()).unwrap();
+            for sol in previous_solutions.iter() {
+                output::write_solution(&mut buf, sol, &previous, &config).unwrap();
+            }
+
+            let previous_index = Index::build(&previous, &config);
+            let new = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+            let mut maps = prepare::append_queries(previous, new, &config).expect("Couldn't append the new strings.");
+            let new_solutions = Overlapper::new(&config, &maps).index(&previous_index).solve_update();
+            assert!(new_solutions.iter().all(|s| maps.is_query(s.id_a) || maps.is_query(s.id_b)));
+            solve_indexed(&config, &maps, &previous_index, modes::default_mode()).expect("Couldn't write output.");
+            let updated = read_lines(&config.output);
+            assert_eq!(updated.len(), previous_solutions.len() + new_solutions.len());
+
+            // the same as solving for all the strings at once
+            maps.first_query_id = 0;
+            let mut all = Vec::new();
+            for sol in Overlapper::new(&config, &maps).solve().iter() {
+                output::write_solution(&mut all, sol, &maps, &config).unwrap();
+            }
+            let all : Vec<String> = String::from_utf8(all).unwrap().lines().map(|l| l.to_owned()).collect();
+            assert_eq!(updated, all);
+        }
+    }
+
+    #[test]
+    fn library_api() {
+        let config = Config{
+            err_rate :      0.02,
+            thresh :        4,
+            worker_threads: 1,
+            n_alphabet:     false,
+            ..Config::default()
+        };
+        let reads = vec![
+            ("x", b"ACGTAGGTCA".to_vec()),
+            ("y", b"GGTCAACGTTGCA".to_vec()),
+        ];
+        let maps = prepare::prepare_reads(reads, &config).expect("Couldn't interpret data.");
+        let solutions = Overlapper::new(&config, &maps)
+            .mode(modes::get_mode("valimaki").unwrap()).unwrap()
+            .solve();
+        let mut results : HashSet<GoodSolution> = HashSet::new();
+        for s in solutions.iter() {
+            results.insert(GoodSolution{
+                a_nm: maps.get_name_for(s.id_a).to_owned(),
+                b_nm: maps.get_name_for(s.id_b).to_owned(),
+                or: s.orientation.clone(),
+                oha: s.overhang_left_a,
+                ohb: s.overhang_right_b,
+                ola: s.overlap_a,
+                olb: s.overlap_b,
+                err: s.errors,
+            });
+        }
+        let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+        should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:5, ohb:8, ola:5, olb:5, err:0});
+        panic_if_solutions_missing(results, should_contain);
+    }
+
+    #[test]
+    fn errors() {
+        use crate::Error;
+        assert!(matches!(modes::get_mode("nonexistent"), Err(Error::Parameter(_))));
+        assert!(matches!(modes::get_mode("kucherov_0"), Err(Error::Parameter(_))));
+        assert!(matches!(modes::get_mode("kucherov_x"), Err(Error::Parameter(_))));
+        assert!(matches!(modes::get_mode("scheme_./test_input/nonexistent.toml"), Err(Error::Io(_))));
+
+        let mut config = Config{
+            input  :        "./test_input/nonexistent.fasta".to_owned(),
+            output  :       output_path("nonexistent/errors.txt"),
+            err_rate :      0.02,
+            thresh :        4,
+            worker_threads: 1,
+            normalisation:  Normalisation::Strict,
+            ..Config::default()
+        };
+        let err = prepare::read_and_prepare(&config.input, &config).unwrap_err();
+        assert!(matches!(err, Error::Io(_)) && err.to_string().contains(&config.input));
+        config.input = "./test_input/iupac.fasta".to_owned();
+        let err = prepare::read_and_prepare(&config.input, &config).unwrap_err();
+        assert!(matches!(err, Error::Input(_)));
+
+        config.normalisation = Normalisation::Mask;
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let err = solve(&config, &maps, modes::default_mode()).unwrap_err();
+        assert!(matches!(err, Error::Io(_)) && err.to_string().contains(&config.output));
+        // search schemes need --max_errors
+        let scheme = modes::get_mode("scheme_./test_input/scheme_k2.toml").unwrap();
+        assert!(matches!(Overlapper::new(&config, &maps).mode(scheme), Err(Error::Parameter(_))));
+
+        let codes : HashSet<i32> = [Error::Input(String::new()), Error::Parameter(String::new()),
+                                    Error::Io(std::io::Error::from(std::io::ErrorKind::NotFound))]
+            .iter().map(|e| e.exit_code()).collect();
+        assert_eq!(codes.len(), 3);
+        assert!(!codes.contains(&0));
+    }
+
     #[test]
     fn modified_levenshtein() {
         use crate::verification::modified_levenshtein;
+        let unit = EditCosts::unit();
+
+        assert_eq!(modified_levenshtein(b"", b"", Some(b'N'), &unit), 0);
+        assert_eq!(modified_levenshtein(b"A", b"A", Some(b'N'), &unit), 0);
+        assert_eq!(modified_levenshtein(b"N", b"N", Some(b'N'), &unit), 1);
+        assert_eq!(modified_levenshtein(b"AA", b"AA", Some(b'N'), &unit), 0);
+        assert_eq!(modified_levenshtein(b"AN", b"AN", Some(b'N'), &unit), 1);
+        assert_eq!(modified_levenshtein(b"AA", b"ATA", Some(b'N'), &unit), 1);
+        assert_eq!(modified_levenshtein(b"ATA", b"AA", Some(b'N'), &unit), 1);
+        assert_eq!(modified_levenshtein(b"AAAAA", b"CAAAC", Some(b'N'), &unit), 2);
+        assert_eq!(modified_levenshtein(b"TTTTA", b"TTTT", Some(b'N'), &unit), 2);
+        assert_eq!(modified_levenshtein(b"G", b"", Some(b'N'), &unit), u32::MAX);
+        assert_eq!(modified_levenshtein(b"GG", b"G", Some(b'N'), &unit), u32::MAX);
+    }
+
+    #[test]
+    fn bounded_modified_levenshtein() {
+        use crate::verification::{modified_levenshtein, bounded_modified_levenshtein, bounded_modified_levenshtein_alignment};
+        // every string of up to 5 symbols over a small alphabet (with wildcard)
+        let mut strings : Vec<Vec<u8>> = vec![vec![]];
+        let mut start = 0;
+        for _ in 0..5 {
+            let end = strings.len();
+            for i in start..end {
+                for &c in b"ACN".iter() {
+                    let mut longer = strings[i].clone();
+                    longer.push(c);
+                    strings.push(longer);
+                }
+            }
+            start = end;
+        }
+        for costs in [EditCosts::unit(), EditCosts{substitution : 1, insertion : 2, deletion : 3},
+                      EditCosts{substitution : 3, insertion : 1, deletion : 1}].iter() {
+            for a in strings.iter() {
+                for b in strings.iter() {
+                    let errors = modified_levenshtein(a, b, Some(b'N'), costs);
+                    for k_limit in 0..7 {
+                        let expected = if errors <= k_limit {Some(errors)} else {None};
+                        assert_eq!(bounded_modified_levenshtein(a, b, Some(b'N'), costs, k_limit), expected);
+                        let aligned = bounded_modified_levenshtein_alignment(a, b, Some(b'N'), costs, k_limit);
+                        assert_eq!(aligned.map(|(errors, _)| errors), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_hamming() {
+        use crate::verification::bounded_hamming;
+        assert_eq!(bounded_hamming(b"", b"", Some(b'N'), 0), Some(0));
+        assert_eq!(bounded_hamming(b"ACGTACGTAC", b"ACGTACGTAC", Some(b'N'), 0), Some(0));
+        assert_eq!(bounded_hamming(b"ACGTNCGTAC", b"ACGTNCGTAC", Some(b'N'), 1), Some(1));
+        assert_eq!(bounded_hamming(b"ACGTNCGTAC", b"ACGTNCGTAC", None, 1), Some(0));
+        assert_eq!(bounded_hamming(b"ACGTACGTAC", b"TCGTACGTAA", Some(b'N'), 1), None);
+
+        // strings spanning several words, with mismatches in and between them
+        let a : Vec<u8> = b"ACGTN".iter().cycle().take(37).cloned().collect();
+        for step in 1..6 {
+            let b : Vec<u8> = a.iter().enumerate()
+                .map(|(i, &c)| if i % step == 0 {b'G'} else {c})
+                .collect();
+            let expected = a.iter().zip(b.iter()).filter(|&(&x, &y)| x != y || x == b'N').count() as u32;
+            for max_mismatches in 0..40 {
+                let bounded = if expected <= max_mismatches {Some(expected)} else {None};
+                assert_eq!(bounded_hamming(&a, &b, Some(b'N'), max_mismatches), bounded);
+            }
+        }
+    }
+
+    #[test]
+    fn edit_costs() {
+        use crate::verification::{modified_levenshtein, bounded_modified_levenshtein_alignment};
+        let costs = EditCosts{substitution : 1, insertion : 2, deletion : 3};
+        assert_eq!(modified_levenshtein(b"AA", b"ATA", Some(b'N'), &costs), 2);
+        assert_eq!(modified_levenshtein(b"ATA", b"AA", Some(b'N'), &costs), 3);
+        assert_eq!(modified_levenshtein(b"AAAAA", b"CAAAC", Some(b'N'), &costs), 2);
+        let costs = EditCosts{substitution : 3, insertion : 1, deletion : 1};
+        assert_eq!(modified_levenshtein(b"AAAAA", b"CAAAC", Some(b'N'), &costs), 6);
+        assert_eq!(modified_levenshtein(b"ACGTA", b"AGCTA", Some(b'N'), &costs), 2);
+        assert_eq!(bounded_modified_levenshtein_alignment(b"ACGTA", b"AGCTA", Some(b'N'), &costs, 2).unwrap().0, 2);
+        assert_eq!(bounded_modified_levenshtein_alignment(b"ACGTA", b"AGCTA", Some(b'N'), &costs, 1), None);
+
+        // the solution of edit.fasta has one insertion
+        let mut config = Config{
+            input  :        "./test_input/edit.fasta".to_owned(),
+            output  :       output_path("edit_costs.txt"),
+            err_rate :      0.2,
+            thresh :        5,
+            worker_threads: 1,
+            edit_distance : true,
+            n_alphabet:     false,
+            costs :         EditCosts{substitution : 1, insertion : 2, deletion : 1},
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        assert!(!results.iter().any(|s| s.ola == 6 && s.olb == 7));
+        config.err_rate = 0.3;
+        solve(&config, &maps, modes::default_mode()).expect("Couldn't write output.");
+        let results = read_output(&config.output);
+        let mut should_contain : HashSet<GoodSolution> = HashSet::new();
+        should_contain.insert(GoodSolution{a_nm:"x".to_owned(), b_nm:"y".to_owned(), or:Normal, oha:7, ohb:7, ola:6, olb:7, err:2});
+        panic_if_solutions_missing(results, should_contain);
+    }
 
-        assert_eq!(modified_levenshtein(b"", b""), 0);
-        assert_eq!(modified_levenshtein(b"A", b"A"), 0);
-        assert_eq!(modified_levenshtein(b"N", b"N"), 1);
-        assert_eq!(modified_levenshtein(b"AA", b"AA"), 0);
-        assert_eq!(modified_levenshtein(b"AN", b"AN"), 1);
-        assert_eq!(modified_levenshtein(b"AA", b"ATA"), 1);
-        assert_eq!(modified_levenshtein(b"ATA", b"AA"), 1);
-        assert_eq!(modified_levenshtein(b"AAAAA", b"CAAAC"), 2);
-        assert_eq!(modified_levenshtein(b"TTTTA", b"TTTT"), 2);
-        assert_eq!(modified_levenshtein(b"G", b""), std::u32::MAX);
-        assert_eq!(modified_levenshtein(b"GG", b"G"), std::u32::MAX);
+    #[test]
+    fn weighted_search() {
+        use crate::search::GeneratesCandidates;
+        use crate::structs::solutions::Solution;
+        use crate::{verification, solution_comparator};
+        // the search charges each branch its own cost. As costs are at least 1, the candidates of a search with unit costs
+        // include those of every solution with weighted costs, so verifying them finds the same solutions
+        let mut config = Config{
+            input  :        "./test_input/small_dataset.fasta".to_owned(),
+            err_rate :      0.25,
+            thresh :        4,
+            worker_threads: 1,
+            reversals :     true,
+            inclusions :    true,
+            edit_distance : true,
+            ..Config::default()
+        };
+        let maps = prepare::read_and_prepare(&config.input, &config).expect("Couldn't interpret data.");
+        let index = Index::build(&maps, &config);
+        let fm = index.fm_index();
+        let mode = modes::default_mode();
+        let unit_candidates : Vec<_> = maps.query_ids()
+            .map(|id_a| (id_a, fm.generate_candidates(maps.get_string(id_a), &config, &maps, id_a, &index, &mode, false, None)))
+            .collect();
+
+        config.costs = EditCosts{substitution : 3, insertion : 1, deletion : 2};
+        let mut expected : Vec<Solution> = unit_candidates.into_iter()
+            .flat_map(|(id_a, candidates)| verification::verify_all(id_a, candidates, &config, &maps))
+            .collect();
+        expected.sort_by(|a, b| solution_comparator(a, b, &maps));
+        expected.dedup_by(|x, y| solution_comparator(x, y, &maps) == std::cmp::Ordering::Equal);
+        let solutions = Overlapper::new(&config, &maps).index(&index).solve();
+        assert!(!solutions.is_empty());
+        assert_eq!(solutions, expected);
     }
 
     #[test]
@@ -352,20 +1510,22 @@ mod tests {
             let err_iter = ErrIterator{next:0.5};
             for err_rate in err_iter {
                 for thresh in 4..(patt_len as f32 * 0.5)as i32 {
-                    let blocks_lengths = mode.get_block_lengths(patt_len, err_rate, thresh);
-                    assert!(!(blocks_lengths.contains(&0))); //no-char blocks would mess with the id lookup
-                    let mut block_id_lookup = search::get_block_id_lookup(&blocks_lengths);
-                    block_id_lookup.reverse();
-                    for pref_len in thresh..patt_len+1{
-                        let pref_blocks = block_id_lookup[(pref_len-1) as usize] + 1; //#blocks is block index + 1
-//                        let effective_pref_blocks = filter_func(pref_blocks-1, blocks_lengths.len() as i32);
-                        let max_allowed_err = (pref_len as f32 * err_rate).floor() as i32;
-
-//                        let filter_permits = filter_func(pref_in_blocks, blocks_lengths.len() as i32);
-                        if !(pref_blocks >= max_allowed_err + guaranteed_extra_blocks) { // must allow K+1 or more
-                            panic!("\nfilter not lenient enough for patt_len {} err_rate {} \
-                            thresh {} pref_len {}.\nBlock lens is {:?}. pref in {} blocks, permitted {} errors.\n",
-                            patt_len, err_rate, thresh, pref_len, &blocks_lengths, pref_blocks, max_allowed_err);
+                    for &max_errors in [None, Some(0), Some(3)].iter() {
+                        let blocks_lengths = mode.get_block_lengths(patt_len, err_rate, thresh, max_errors);
+                        assert!(!(blocks_lengths.contains(&0))); //no-char blocks would mess with the id lookup
+                        let mut block_id_lookup = search::get_block_id_lookup(&blocks_lengths);
+                        block_id_lookup.reverse();
+                        for pref_len in thresh..patt_len+1{
+                            let pref_blocks = block_id_lookup[(pref_len-1) as usize] + 1; //#blocks is block index + 1
+//                            let effective_pref_blocks = filter_func(pref_blocks-1, blocks_lengths.len() as i32);
+                            let max_allowed_err = min((pref_len as f32 * err_rate).floor() as i32, max_errors.unwrap_or(i32::MAX));
+
+//                            let filter_permits = filter_func(pref_in_blocks, blocks_lengths.len() as i32);
+                            if pref_blocks < max_allowed_err + guaranteed_extra_blocks { // must allow K+1 or more
+                                panic!("\nfilter not lenient enough for patt_len {} err_rate {} \
+                                thresh {} pref_len {}.\nBlock lens is {:?}. pref in {} blocks, permitted {} errors.\n",
+                                patt_len, err_rate, thresh, pref_len, &blocks_lengths, pref_blocks, max_allowed_err);
+                            }
                         }
                     }
                 }
@@ -420,15 +1580,15 @@ mod tests {
         let mut found : HashSet<&GoodSolution> = HashSet::new();
         let mut missing : HashSet<&GoodSolution> = HashSet::new();
         for x in should_contain.iter(){
-            if solutions.contains(&x){
+            if solutions.contains(x){
                 found.insert(x);
             }else{
                 missing.insert(x);
             }
         }
-        if missing.len() > 0{
-            panic!(format!("MISSING {} / {} solutions\n{:#?}. all solutions {:#?}",
-                           missing.len(), should_contain.len(), &missing, &solutions));
+        if !missing.is_empty(){
+            panic!("MISSING {} / {} solutions\n{:#?}. all solutions {:#?}",
+                   missing.len(), should_contain.len(), &missing, &solutions);
         }
     }
 }
@@ -1,13 +1,12 @@
-use bio::alignment::distance::{hamming, levenshtein};
+use bio::alignment::distance::levenshtein;
 
 
-use std;
-use std::cmp::max;
+use std::cmp::{min, max};
 use std::collections::HashSet;
+use std::convert::TryInto;
 
-use crate::structs::solutions::{Candidate, Solution};
-use crate::structs::run_config::{Config, Maps};
-use crate::search;
+use crate::structs::solutions::{Candidate, Solution, Cigar};
+use crate::structs::run_config::{Config, Maps, EditCosts};
 use crate::useful::{relative_orientation, companion_id, for_reversed_string};
 
 
@@ -31,6 +30,19 @@ pub fn verify_all(id_a : usize, candidates : HashSet<Candidate>, config : &Confi
     solution_set
 }
 
+/*
+The counterpart of verify_all for candidates of an exact search (see GeneratesCandidates::generate_exact_candidates).
+Each of them is known to match without errors, so each becomes a solution with K=0 as is.
+*/
+pub fn accept_all(id_a : usize, candidates : HashSet<Candidate>, config : &Config, maps : &Maps) -> HashSet<Solution> {
+    candidates.into_iter()
+        .map(|c| {
+            let cigar = if config.cigar {Some(vec![(c.a2(), b'M')])} else {None};
+            solution_from_candidate(c, id_a, 0, cigar, maps, config)
+        })
+        .collect()
+}
+
 /*
 Returns a solution corresponding with the given candidate if appropriate.
 This function performs the CHECK if the candidate verifies.
@@ -50,62 +62,326 @@ a2 and b2 are the overlapping sections, and a1,a3,b1,b3 are the lengths of parts
 pub fn verify(id_a : usize, c : Candidate, config : &Config, maps : &Maps) -> Option<Solution>{
     let a_len = maps.get_length(id_a);
     assert_eq!(c.a3(a_len), 0);
+    if !config.overlap_lengths_allowed(c.overlap_a, c.overlap_b){
+        return None;
+    }
     //b3 is usize, so implicitly b3 >= 0
     let a_part : &[u8] = &maps.get_string(id_a)  [c.a1()..(c.a1()+c.a2())];
     let b_part : &[u8] = &maps.get_string(c.id_b)[c.b1()..(c.b1()+c.b2())];
-    let k_limit = (config.err_rate*(max(c.overlap_a, c.overlap_b) as f32)).floor() as u32;
+    let k_limit = config.error_limit(max(c.overlap_a, c.overlap_b));
+    // insertions and deletions are as seen from the A of the output, which may be this B
+    let costs = if names_in_order(id_a, c.id_b, maps) {config.costs} else {config.costs.swapped()};
 
-    let errors : u32 = if config.edit_distance{
-        modified_levenshtein(a_part, b_part)
+    let (errors, cigar) = if config.edit_distance{
+        // most candidates fail, so the alignment is only computed (and its moves kept) for those that don't
+        let errors = bounded_modified_levenshtein(a_part, b_part, config.alphabet.wildcard, &costs, k_limit)?;
+        if config.cigar {
+            let (errors, cigar) = bounded_modified_levenshtein_alignment(a_part, b_part, config.alphabet.wildcard, &costs, k_limit)?;
+            (errors, Some(cigar))
+        } else {
+            (errors, None)
+        }
     }else{
         assert!(a_part.len() == b_part.len());
-        hamming(a_part, b_part) as u32
+        let mismatches = bounded_hamming(a_part, b_part, config.alphabet.wildcard, k_limit / costs.substitution)?;
+        let cigar = if config.cigar {Some(vec![(a_part.len(), b'M')])} else {None};
+        (mismatches * costs.substitution, cigar)
     };
     if errors <= k_limit{
-        Some(solution_from_candidate(c, id_a, errors, maps, config))
+        Some(solution_from_candidate(c, id_a, errors, cigar, maps, config))
     }else{
         None
     }
 }
 
 
+/*
+The number of positions at which the (equally long) strings differ if it is at most max_mismatches, otherwise None.
+As in the search, the wildcard symbol (if any) is a mismatch even where both strings have it.
+Compares 8 symbols at a time (see mismatch_mask), checking the count against the limit after each word.
+*/
+pub fn bounded_hamming(a_part : &[u8], b_part : &[u8], wildcard : Option<u8>, max_mismatches : u32) -> Option<u32> {
+    assert_eq!(a_part.len(), b_part.len());
+    let wildcard_word = wildcard.map(|w| u64::from_ne_bytes([w; 8]));
+    let mut mismatches = 0;
+    let (a_words, b_words) = (a_part.chunks_exact(8), b_part.chunks_exact(8));
+    let (a_tail, b_tail) = (a_words.remainder(), b_words.remainder());
+    for (a_word, b_word) in a_words.zip(b_words) {
+        let a_word = u64::from_ne_bytes(a_word.try_into().unwrap());
+        let b_word = u64::from_ne_bytes(b_word.try_into().unwrap());
+        mismatches += mismatch_mask(a_word, b_word, wildcard_word).count_ones();
+        if mismatches > max_mismatches {
+            return None;
+        }
+    }
+    for (&a, &b) in a_tail.iter().zip(b_tail.iter()) {
+        if a != b || Some(a) == wildcard {
+            mismatches += 1;
+        }
+    }
+    if mismatches <= max_mismatches {Some(mismatches)} else {None}
+}
+
+/*
+for 8 packed symbols of each string, a word with the high bit of each byte set IFF the symbols there mismatch
+(they differ or the one of A is the wildcard, packed 8 times into wildcard_word).
+*/
+#[inline]
+fn mismatch_mask(a_word : u64, b_word : u64, wildcard_word : Option<u64>) -> u64 {
+    const LOW_BITS : u64 = 0x7f7f_7f7f_7f7f_7f7f;
+    const HIGH_BITS : u64 = !LOW_BITS;
+    // high bit of each byte set IFF the byte is nonzero
+    let nonzero_bytes = |x : u64| (((x & LOW_BITS) + LOW_BITS) | x) & HIGH_BITS;
+    let differ = nonzero_bytes(a_word ^ b_word);
+    match wildcard_word {
+        Some(w) => differ | (!nonzero_bytes(a_word ^ w) & HIGH_BITS),
+        None => differ,
+    }
+}
+
 /*
 A custom levenshtein distance where the first and last characters of each overlap are forced to be substitutions
 As such, if the incoming strings have lengths
+The wildcard symbol (if any) at the first or last position is an error even where both strings have it.
+Each substitution, insertion and deletion adds its cost to the distance.
 */
-pub fn modified_levenshtein(a_part : &[u8], b_part : &[u8]) -> u32 {
+pub fn modified_levenshtein(a_part : &[u8], b_part : &[u8], wildcard : Option<u8>, costs : &EditCosts) -> u32 {
     if a_part.len() == b_part.len() && a_part.len() <= 2{
         //case where strings are the same length, but are of length 0, 1 or 2 (no indels possible)
         let mut errs = 0;
-        if a_part.len() >= 1 {
-            errs += error_at_pos_in_both(a_part, b_part, true);
+        if !a_part.is_empty() {
+            errs += error_at_pos_in_both(a_part, b_part, true, wildcard);
         }
         if a_part.len() >= 2 {
-            errs += error_at_pos_in_both(a_part, b_part, false);
+            errs += error_at_pos_in_both(a_part, b_part, false, wildcard);
         }
-        return errs;
+        return errs * costs.substitution;
     }
     if a_part.len() < 2 || b_part.len() < 2{
         // undefined distance. return max possible value
-        return std::u32::MAX;
+        return u32::MAX;
     }
     //below this line: a_overlap_end >= 2 && b_overlap_end >= 2
-    let first_char_err = error_at_pos_in_both(a_part, b_part, true);
-    let last_char_err = error_at_pos_in_both(a_part, b_part, false);
-    levenshtein(&a_part[1..a_part.len()-1], &b_part[1..b_part.len()-1])
-        + first_char_err + last_char_err
+    let first_char_err = error_at_pos_in_both(a_part, b_part, true, wildcard);
+    let last_char_err = error_at_pos_in_both(a_part, b_part, false, wildcard);
+    let (a_inner, b_inner) = (&a_part[1..a_part.len()-1], &b_part[1..b_part.len()-1]);
+    let inner = if costs.is_unit() {
+        levenshtein(a_inner, b_inner)
+    } else {
+        weighted_levenshtein(a_inner, b_inner, costs)
+    };
+    inner + (first_char_err + last_char_err) * costs.substitution
+}
+
+/*
+As modified_levenshtein(), but only interested in distances of at most k_limit, returning None for larger ones.
+The inner distance is computed by banded_levenshtein, which only fills the cells that can still be within
+the limit and stops as soon as none of them are.
+*/
+pub fn bounded_modified_levenshtein(a_part : &[u8], b_part : &[u8], wildcard : Option<u8>,
+                                    costs : &EditCosts, k_limit : u32) -> Option<u32> {
+    if (a_part.len() == b_part.len() && a_part.len() <= 2) || a_part.len() < 2 || b_part.len() < 2 {
+        // no indels possible (or no distance defined), so nothing to save here
+        let errors = modified_levenshtein(a_part, b_part, wildcard, costs);
+        return if errors <= k_limit {Some(errors)} else {None};
+    }
+    let end_errors = (error_at_pos_in_both(a_part, b_part, true, wildcard)
+        + error_at_pos_in_both(a_part, b_part, false, wildcard)) * costs.substitution;
+    if end_errors > k_limit {
+        return None;
+    }
+    let (a_inner, b_inner) = (&a_part[1..a_part.len()-1], &b_part[1..b_part.len()-1]);
+    banded_levenshtein(a_inner, b_inner, costs, k_limit - end_errors).map(|inner| inner + end_errors)
+}
+
+/*
+weighted levenshtein distance (see weighted_levenshtein) if it is at most k_limit, otherwise None.
+Ukkonen's cutoff: an alignment with a cell j-i columns right of the diagonal has at least
+that many more insertions than deletions up to there (likewise for left and deletions),
+so only a band of k_limit/insertion diagonals to the right and k_limit/deletion to the left can be within the limit.
+A row without any cell within the limit ends the computation early.
+*/
+fn banded_levenshtein(a : &[u8], b : &[u8], costs : &EditCosts, k_limit : u32) -> Option<u32> {
+    let right = (k_limit / costs.insertion) as usize;
+    let left = (k_limit / costs.deletion) as usize;
+    if b.len() > a.len() + right || a.len() > b.len() + left {
+        // the end of the alignment lies outside of the band
+        return None;
+    }
+    // cells outside of the band. never reached by adding costs within the limit
+    let beyond = k_limit + 1;
+    let mut prev : Vec<u32> = (0..(b.len() + 1))
+        .map(|j| if j <= right {j as u32 * costs.insertion} else {beyond})
+        .collect();
+    let mut curr : Vec<u32> = vec![beyond; b.len() + 1];
+    for i in 1..(a.len() + 1) {
+        let first = i.saturating_sub(left);
+        let last = min(b.len(), i + right);
+        if first > 0 {
+            curr[first-1] = beyond;
+        }
+        let mut row_min = beyond;
+        for j in first..(last + 1) {
+            curr[j] = if j == 0 {
+                i as u32 * costs.deletion
+            } else {
+                let diagonal = prev[j-1] + if a[i-1] == b[j-1] {0} else {costs.substitution};
+                min(diagonal, min(prev[j] + costs.deletion, curr[j-1] + costs.insertion))
+            };
+            curr[j] = min(curr[j], beyond);
+            row_min = min(row_min, curr[j]);
+        }
+        if last < b.len() {
+            curr[last+1] = beyond;
+        }
+        if row_min > k_limit {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    if prev[b.len()] <= k_limit {Some(prev[b.len()])} else {None}
+}
+
+// levenshtein distance with the given cost for each operation (see EditCosts)
+fn weighted_levenshtein(a : &[u8], b : &[u8], costs : &EditCosts) -> u32 {
+    let mut prev : Vec<u32> = (0..(b.len() as u32 + 1)).map(|j| j * costs.insertion).collect();
+    let mut curr : Vec<u32> = vec![0; b.len() + 1];
+    for i in 1..(a.len() + 1) {
+        curr[0] = i as u32 * costs.deletion;
+        for j in 1..(b.len() + 1) {
+            let diagonal = prev[j-1] + if a[i-1] == b[j-1] {0} else {costs.substitution};
+            curr[j] = min(diagonal, min(prev[j] + costs.deletion, curr[j-1] + costs.insertion));
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/*
+As bounded_modified_levenshtein(), but also returns an alignment with that many errors (see Cigar).
+The first and last symbols are always aligned to one another, with a weighted
+levenshtein alignment in between (see banded_levenshtein_alignment).
+*/
+pub fn bounded_modified_levenshtein_alignment(a_part : &[u8], b_part : &[u8], wildcard : Option<u8>,
+                                              costs : &EditCosts, k_limit : u32) -> Option<(u32, Cigar)> {
+    if (a_part.len() == b_part.len() && a_part.len() <= 2) || a_part.len() < 2 || b_part.len() < 2 {
+        // no indels possible (or no distance defined)
+        let errors = modified_levenshtein(a_part, b_part, wildcard, costs);
+        return if errors <= k_limit {Some((errors, vec![(a_part.len(), b'M')]))} else {None};
+    }
+    let end_errors = (error_at_pos_in_both(a_part, b_part, true, wildcard)
+        + error_at_pos_in_both(a_part, b_part, false, wildcard)) * costs.substitution;
+    if end_errors > k_limit {
+        return None;
+    }
+    let (a_inner, b_inner) = (&a_part[1..a_part.len()-1], &b_part[1..b_part.len()-1]);
+    let (errors, inner) = banded_levenshtein_alignment(a_inner, b_inner, costs, k_limit - end_errors)?;
+    let mut cigar : Cigar = vec![(1, b'M')];
+    for (len, op) in inner {
+        push_cigar_op(&mut cigar, len, op);
+    }
+    push_cigar_op(&mut cigar, 1, b'M');
+    Some((errors + end_errors, cigar))
+}
+
+/*
+As banded_levenshtein, along with one alignment that has this distance.
+The operation that reached each cell of the band is kept to trace the alignment back, so the memory it takes
+grows with the length of A times the width of the band, rather than with the lengths of both strings.
+Cells within the limit hold the same distances as they would in the full matrix, and so does the alignment.
+*/
+fn banded_levenshtein_alignment(a : &[u8], b : &[u8], costs : &EditCosts, k_limit : u32) -> Option<(u32, Cigar)> {
+    let right = min((k_limit / costs.insertion) as usize, b.len());
+    let left = min((k_limit / costs.deletion) as usize, a.len());
+    if b.len() > a.len() + right || a.len() > b.len() + left {
+        return None;
+    }
+    let beyond = k_limit + 1;
+    // cell (i, j) of the band of row i, from j == i-left to j == i+right
+    let band = left + right + 1;
+    let at = |i : usize, j : usize| i * band + j + left - i;
+    let mut moves : Vec<u8> = vec![b'M'; (a.len() + 1) * band];
+    let mut prev : Vec<u32> = (0..(b.len() + 1))
+        .map(|j| if j <= right {j as u32 * costs.insertion} else {beyond})
+        .collect();
+    let mut curr : Vec<u32> = vec![beyond; b.len() + 1];
+    for j in 1..(right + 1) {
+        moves[at(0, j)] = b'I';
+    }
+    for i in 1..(a.len() + 1) {
+        let first = i.saturating_sub(left);
+        let last = min(b.len(), i + right);
+        if first > 0 {
+            curr[first-1] = beyond;
+        }
+        let mut row_min = beyond;
+        for j in first..(last + 1) {
+            let (cost, op) = if j == 0 {
+                (i as u32 * costs.deletion, b'D')
+            } else {
+                let diagonal = prev[j-1] + if a[i-1] == b[j-1] {0} else {costs.substitution};
+                let only_a = prev[j] + costs.deletion;
+                let only_b = curr[j-1] + costs.insertion;
+                if diagonal <= only_a && diagonal <= only_b {
+                    (diagonal, b'M')
+                } else if only_a <= only_b {
+                    (only_a, b'D')
+                } else {
+                    (only_b, b'I')
+                }
+            };
+            curr[j] = min(cost, beyond);
+            moves[at(i, j)] = op;
+            row_min = min(row_min, curr[j]);
+        }
+        if last < b.len() {
+            curr[last+1] = beyond;
+        }
+        if row_min > k_limit {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    if prev[b.len()] > k_limit {
+        return None;
+    }
+
+    let mut backwards : Cigar = Vec::new();
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 || j > 0 {
+        let op = moves[at(i, j)];
+        match op {
+            b'M' => {i -= 1; j -= 1;},
+            b'D' => i -= 1,
+            _ => j -= 1,
+        }
+        push_cigar_op(&mut backwards, 1, op);
+    }
+    backwards.reverse();
+    Some((prev[b.len()], backwards))
 }
 
 #[inline]
-fn error_at_pos_in_both(a_part : &[u8], b_part : &[u8], first : bool) -> u32 {
-    assert!(a_part.len() >= 1);
-    assert!(b_part.len() >= 1);
+fn push_cigar_op(cigar : &mut Cigar, len : usize, op : u8) {
+    if let Some(last) = cigar.last_mut() {
+        if last.1 == op {
+            last.0 += len;
+            return;
+        }
+    }
+    cigar.push((len, op));
+}
+
+#[inline]
+fn error_at_pos_in_both(a_part : &[u8], b_part : &[u8], first : bool, wildcard : Option<u8>) -> u32 {
+    assert!(!a_part.is_empty());
+    assert!(!b_part.is_empty());
     let a_ind = if first {0} else {a_part.len()-1};
     let b_ind = if first {0} else {b_part.len()-1};
     if a_part[a_ind] != b_part[b_ind] {
         1
     } else {
-        if a_part[a_ind] == search::READ_ERR { 1 } else { 0 }
+        if Some(a_part[a_ind]) == wildcard { 1 } else { 0 }
     }
 }
 
@@ -120,20 +396,21 @@ but Candidates are largely INTERNAL (as verifying them requires the use of the i
 
 *See annotation for verify() above for an explanation of a1,a2,a3,b1,b2,b3 etc. used here.
 */
-fn solution_from_candidate(c : Candidate, id_a : usize, errors : u32,
+fn solution_from_candidate(c : Candidate, id_a : usize, errors : u32, cigar : Option<Cigar>,
                            maps : &Maps, config : &Config) -> Solution {
     let a_len = maps.get_length(id_a);
     let b_len = maps.get_length(c.id_b);
     let orientation = relative_orientation(id_a, c.id_b, config.reversals);
     let mut sol = Solution{
-        id_a : id_a,
+        id_a,
         id_b : c.id_b,
-        orientation : orientation,
+        orientation,
         overlap_a : c.overlap_a,
         overlap_b : c.overlap_b,
         overhang_left_a : c.overhang_left_a,
         overhang_right_b : (c.b3(b_len) as i32) - (c.a3(a_len) as i32),
-        errors : errors,
+        errors,
+        cigar,
     };
     translate_solution_to_external(&mut sol, config, maps);
     sol
@@ -141,8 +418,14 @@ fn solution_from_candidate(c : Candidate, id_a : usize, errors : u32,
 
 #[inline]
 fn id_order_ok(sol : &Solution, maps : &Maps) -> bool {
-    maps.get_name_for(sol.id_a).
-        cmp(maps.get_name_for(sol.id_b))
+    names_in_order(sol.id_a, sol.id_b, maps)
+}
+
+// whether a solution of these two strings keeps them as A and B in the output (see translate_solution_to_external)
+#[inline]
+fn names_in_order(id_a : usize, id_b : usize, maps : &Maps) -> bool {
+    maps.get_name_for(id_a).
+        cmp(maps.get_name_for(id_b))
         != std::cmp::Ordering::Greater
 }
 